@@ -0,0 +1,104 @@
+// Base64 as used by the `!!binary` type, see https://yaml.org/type/binary.html
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some(u32::from(c - b'A')),
+        b'a'..=b'z' => Some(u32::from(c - b'a') + 26),
+        b'0'..=b'9' => Some(u32::from(c - b'0') + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode base64 text, ignoring whitespace such as the line breaks of a
+/// folded or literal scalar. Padding is optional.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut padding = 0;
+    for c in text.bytes() {
+        match c {
+            b' ' | b'\t' | b'\n' | b'\r' => continue,
+            b'=' => padding += 1,
+            _ if padding > 0 => return None,
+            _ => {
+                acc = (acc << 6) | value(c)?;
+                bits += 6;
+                if bits >= 8 {
+                    bits -= 8;
+                    out.push((acc >> bits) as u8);
+                    acc &= (1 << bits) - 1;
+                }
+            }
+        }
+    }
+    // a single leftover character can't encode a byte, and the padding must
+    // complete the last group
+    if bits >= 6 || acc != 0 || (padding > 0 && (bits / 2 != padding)) {
+        return None;
+    }
+    Some(out)
+}
+
+/// Encode bytes as base64 with padding.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (&[0, 255, 128], "AP+A"),
+        ];
+        for &(bytes, text) in cases {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(" Zm9v\n YmE=\n").unwrap(), b"fooba");
+        assert_eq!(decode("Zm9vYg").unwrap(), b"foob");
+        for bad in &[
+            "Z",
+            "Zm9vY",
+            "Zm9v!",
+            "Zg==Zg==",
+            "Zh==",
+            "Zm9vYg=",
+            "Zm9vYg===",
+        ] {
+            assert_eq!(decode(bad), None, "{}", bad);
+        }
+    }
+}
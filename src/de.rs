@@ -0,0 +1,775 @@
+//! Deserialize Rust values from YAML with serde, when the `serde` feature is
+//! enabled.
+//!
+//! The deserializer reads the parser's events directly rather than loading
+//! a `Yaml` tree first, so errors point at the node that failed.
+//!
+//! ```
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, PartialEq, Debug)]
+//! struct Config {
+//!     name: String,
+//!     port: u16,
+//! }
+//!
+//! let config: Config = yaml_rust::de::from_str("name: web\nport: 8080").unwrap();
+//! assert_eq!(config, Config { name: "web".to_owned(), port: 8080 });
+//!
+//! let err = yaml_rust::de::from_str::<Config>("name: web\nport: -1").unwrap_err();
+//! assert_eq!(err.marker().unwrap().line(), 2);
+//! ```
+
+use crate::base64;
+use crate::parser::{Event, MarkedEventReceiver, Parser};
+use crate::scanner::{Limit, Marker, ScanError, TScalarStyle, TokenType};
+use crate::yaml::{bad_value_info, parse_f64, resolve_core_tag, LoaderOptions, Yaml};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+/// An error from deserializing YAML, or from serializing with
+/// `yaml_rust::ser`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Error {
+    info: String,
+    mark: Option<Marker>,
+    limit: Option<Limit>,
+}
+
+impl Error {
+    fn new(info: &str, mark: Marker) -> Error {
+        Error {
+            info: info.to_owned(),
+            mark: Some(mark),
+            limit: None,
+        }
+    }
+
+    /// Where the node that failed to deserialize starts, or `None` for
+    /// errors that aren't about a node.
+    pub fn marker(&self) -> Option<&Marker> {
+        self.mark.as_ref()
+    }
+
+    /// The error without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The limit that was exceeded, if this error was caused by one.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+
+    // Errors from visitors don't know where they happened, so the node being
+    // visited claims them. Nested nodes claim them first.
+    fn at(mut self, mark: Marker) -> Error {
+        if self.mark.is_none() {
+            self.mark = Some(mark);
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mark {
+            Some(mark) => write!(
+                f,
+                "{} at line {} column {}",
+                self.info,
+                mark.line(),
+                mark.col() + 1,
+            ),
+            None => f.write_str(&self.info),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            info: msg.to_string(),
+            mark: None,
+            limit: None,
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            info: msg.to_string(),
+            mark: None,
+            limit: None,
+        }
+    }
+}
+
+impl From<ScanError> for Error {
+    fn from(e: ScanError) -> Error {
+        Error {
+            limit: e.limit(),
+            ..Error::new(e.info(), *e.marker())
+        }
+    }
+}
+
+// The alias expansion limit of `from_str`.
+const MAX_ALIAS_EXPANSION: usize = 1_000_000;
+
+/// Deserialize an instance of `T` from a YAML stream with one document. An
+/// empty stream deserializes as a null.
+///
+/// Aliases may expand to at most a million nodes in all, so that a small
+/// untrusted document can't expand to billions. Use `from_str_with_options`
+/// to change this limit, or to limit the parser.
+pub fn from_str<T: DeserializeOwned>(source: &str) -> Result<T, Error> {
+    let options = LoaderOptions {
+        max_alias_expansion: Some(MAX_ALIAS_EXPANSION),
+        ..LoaderOptions::default()
+    };
+    from_str_with_options(source, &options)
+}
+
+/// Deserialize an instance of `T` from a YAML stream with one document,
+/// within the limits of `options.parser` and `options.max_alias_expansion`.
+/// The other options only apply to `YamlLoader`.
+///
+/// Each alias the deserializer follows counts as all the nodes of its
+/// anchor, so a document that expands to far more nodes than it contains
+/// fails instead of taking time exponential in its size.
+pub fn from_str_with_options<T: DeserializeOwned>(
+    source: &str,
+    options: &LoaderOptions,
+) -> Result<T, Error> {
+    let mut loader = EventLoader::default();
+    Parser::with_options(source.chars(), options.parser).load(&mut loader, true)?;
+    let doc = match loader.docs.len() {
+        0 => Document {
+            events: vec![(
+                Event::Scalar("~".to_owned(), TScalarStyle::Plain, 0, None),
+                Marker::new(0, 1, 0),
+            )],
+            aliases: HashMap::new(),
+        },
+        1 => loader.docs.pop().unwrap(),
+        _ => {
+            return Err(de::Error::custom(
+                "deserializing from a stream of more than one document is not supported",
+            ))
+        }
+    };
+    let expanded = Cell::new(0);
+    T::deserialize(&mut Deserializer {
+        events: &doc.events,
+        aliases: &doc.aliases,
+        pos: 0,
+        expanded: &expanded,
+        max_expansion: options.max_alias_expansion,
+    })
+}
+
+// The events of one document, without its start and end.
+#[derive(Default)]
+struct Document {
+    events: Vec<(Event, Marker)>,
+    // the position and number of nodes of the node each alias refers to,
+    // by the alias's position
+    aliases: HashMap<usize, (usize, usize)>,
+}
+
+#[derive(Default)]
+struct EventLoader {
+    docs: Vec<Document>,
+    // the position, anchor and number of earlier nodes of each collection
+    // that hasn't ended
+    open: Vec<(usize, usize, usize)>,
+    // the position and number of nodes of each anchored node, once the node
+    // is complete
+    anchors: HashMap<usize, (usize, usize)>,
+    // the number of nodes in the current document, with each alias as one
+    nodes: usize,
+}
+
+impl MarkedEventReceiver for EventLoader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if ev == Event::DocumentStart {
+            self.docs.push(Document::default());
+            self.anchors.clear();
+            self.nodes = 0;
+            return;
+        }
+        let doc = match self.docs.last_mut() {
+            Some(doc) => doc,
+            None => return,
+        };
+        let pos = doc.events.len();
+        match ev {
+            Event::Nothing
+            | Event::StreamStart(_)
+            | Event::StreamEnd
+            | Event::DocumentStart
+            | Event::DocumentEnd => return,
+            // an alias inside its own anchor isn't in `anchors` yet, and is
+            // left unresolved
+            Event::Alias(id) => {
+                if let Some(&target) = self.anchors.get(&id) {
+                    doc.aliases.insert(pos, target);
+                }
+                self.nodes += 1;
+            }
+            Event::Scalar(_, _, aid, _) => {
+                if aid > 0 {
+                    self.anchors.insert(aid, (pos, 1));
+                }
+                self.nodes += 1;
+            }
+            Event::SequenceStart(aid, _) | Event::MappingStart(aid, _) => {
+                self.open.push((pos, aid, self.nodes));
+                self.nodes += 1;
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (start, aid, before) = self.open.pop().unwrap();
+                if aid > 0 {
+                    self.anchors.insert(aid, (start, self.nodes - before));
+                }
+            }
+        }
+        doc.events.push((ev, mark));
+    }
+}
+
+// Resolves a scalar like `YamlLoader` does without a schema.
+fn resolve(v: &str, style: TScalarStyle, tag: &Option<TokenType>) -> Yaml {
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!!" && suffix == "binary" => {
+            match base64::decode(v) {
+                Some(bytes) => Yaml::Binary(bytes),
+                None => Yaml::BadValue,
+            }
+        }
+        Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!!" => {
+            resolve_core_tag(suffix, v.to_owned())
+        }
+        _ if style != TScalarStyle::Plain => Yaml::String(v.to_owned()),
+        Some(_) => Yaml::String(v.to_owned()),
+        None => Yaml::from_str(v),
+    }
+}
+
+struct Deserializer<'a> {
+    events: &'a [(Event, Marker)],
+    aliases: &'a HashMap<usize, (usize, usize)>,
+    pos: usize,
+    // the number of nodes read through aliases so far, shared by the
+    // deserializers for the nodes they refer to
+    expanded: &'a Cell<usize>,
+    max_expansion: Option<usize>,
+}
+
+impl<'a> Deserializer<'a> {
+    fn peek(&self) -> Result<&'a (Event, Marker), Error> {
+        let events = self.events;
+        events
+            .get(self.pos)
+            .ok_or_else(|| de::Error::custom("unexpected end of document"))
+    }
+
+    fn next(&mut self) -> Result<&'a (Event, Marker), Error> {
+        let ev = self.peek()?;
+        self.pos += 1;
+        Ok(ev)
+    }
+
+    // If the next node is an alias, skips it and returns a deserializer for
+    // the node it refers to.
+    fn alias(&mut self) -> Result<Option<Deserializer<'a>>, Error> {
+        match *self.peek()? {
+            (Event::Alias(_), mark) => match self.aliases.get(&self.pos) {
+                Some(&(target, size)) => {
+                    let expanded = self.expanded.get().saturating_add(size);
+                    if self.max_expansion.map_or(false, |max| expanded > max) {
+                        return Err(ScanError::limit_exceeded(mark, Limit::AliasExpansion).into());
+                    }
+                    self.expanded.set(expanded);
+                    self.pos += 1;
+                    Ok(Some(Deserializer {
+                        events: self.events,
+                        aliases: self.aliases,
+                        pos: target,
+                        expanded: self.expanded,
+                        max_expansion: self.max_expansion,
+                    }))
+                }
+                None => Err(Error::new(
+                    "while deserializing an alias, found a recursive anchor",
+                    mark,
+                )),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    // The next node is a null scalar.
+    fn at_null(&self) -> Result<bool, Error> {
+        Ok(match self.peek()?.0 {
+            Event::Scalar(ref v, style, _, ref tag) => resolve(v, style, tag).is_null(),
+            _ => false,
+        })
+    }
+
+    fn skip(&mut self) -> Result<(), Error> {
+        let mut depth = 0;
+        loop {
+            match self.next()?.0 {
+                Event::SequenceStart(..) | Event::MappingStart(..) => depth += 1,
+                Event::SequenceEnd | Event::MappingEnd => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Skips the end of a collection the visitor has read all of.
+    fn end(&mut self, mark: Marker, info: &str) -> Result<(), Error> {
+        match self.next()?.0 {
+            Event::SequenceEnd | Event::MappingEnd => Ok(()),
+            _ => Err(Error::new(info, mark)),
+        }
+    }
+}
+
+impl<'de, 'a, 'b> de::Deserializer<'de> for &'b mut Deserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some(mut de) = self.alias()? {
+            return de.deserialize_any(visitor);
+        }
+        let (ev, mark) = self.next()?;
+        let mark = *mark;
+        match *ev {
+            Event::Scalar(ref v, style, _, ref tag) => match resolve(v, style, tag) {
+                Yaml::Integer(i) => visitor.visit_i64(i),
+                // beyond i64, most visitors accept u64 but not i128
+                Yaml::BigInteger(i) => match u64::try_from(i) {
+                    Ok(u) => visitor.visit_u64(u),
+                    Err(_) => visitor.visit_i128(i),
+                },
+                Yaml::Real(ref r) => visitor.visit_f64(parse_f64(r).unwrap()),
+                Yaml::Boolean(b) => visitor.visit_bool(b),
+                Yaml::Null => visitor.visit_unit(),
+                Yaml::Binary(bytes) => visitor.visit_byte_buf(bytes),
+                Yaml::BadValue => return Err(Error::new(&bad_value_info(tag), mark)),
+                _ => visitor.visit_str(v),
+            }
+            .map_err(|e: Error| e.at(mark)),
+            Event::SequenceStart(..) => {
+                let value = visitor
+                    .visit_seq(SeqAccess { de: &mut *self })
+                    .map_err(|e: Error| e.at(mark))?;
+                self.end(mark, "invalid length, expected fewer elements in sequence")?;
+                Ok(value)
+            }
+            Event::MappingStart(..) => {
+                let value = visitor
+                    .visit_map(MapAccess { de: &mut *self })
+                    .map_err(|e: Error| e.at(mark))?;
+                self.end(mark, "invalid length, expected fewer entries in mapping")?;
+                Ok(value)
+            }
+            _ => Err(Error::new("unexpected event", mark)),
+        }
+    }
+
+    /// Scalars other than nulls and `!!binary` give their text as written,
+    /// so `1.10` is the string `"1.10"` rather than a float.
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some(mut de) = self.alias()? {
+            return de.deserialize_str(visitor);
+        }
+        if let (Event::Scalar(ref v, _, _, ref tag), mark) = *self.peek()? {
+            let binary = match *tag {
+                Some(TokenType::Tag(ref handle, ref suffix)) => {
+                    handle == "!!" && suffix == "binary"
+                }
+                _ => false,
+            };
+            if !binary && !self.at_null()? {
+                self.pos += 1;
+                return visitor.visit_str(v).map_err(|e: Error| e.at(mark));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some(mut de) = self.alias()? {
+            return de.deserialize_option(visitor);
+        }
+        if self.at_null()? {
+            let mark = self.next()?.1;
+            return visitor.visit_none().map_err(|e: Error| e.at(mark));
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are scalars, and other variants are mappings with a
+    /// single entry from the variant name to its value.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if let Some(mut de) = self.alias()? {
+            return de.deserialize_enum(name, variants, visitor);
+        }
+        match *self.peek()? {
+            (Event::Scalar(ref v, ..), mark) => {
+                self.pos += 1;
+                visitor
+                    .visit_enum(v.as_str().into_deserializer())
+                    .map_err(|e: Error| e.at(mark))
+            }
+            (Event::MappingStart(..), mark) => {
+                self.pos += 1;
+                let value = visitor
+                    .visit_enum(EnumAccess { de: &mut *self })
+                    .map_err(|e: Error| e.at(mark))?;
+                self.end(mark, "expected a mapping with a single entry for an enum")?;
+                Ok(value)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.skip()?;
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct
+    }
+}
+
+struct SeqAccess<'a, 'b> {
+    de: &'b mut Deserializer<'a>,
+}
+
+impl<'de, 'a, 'b> de::SeqAccess<'de> for SeqAccess<'a, 'b> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.de.peek()?.0 {
+            Event::SequenceEnd => Ok(None),
+            _ => seed.deserialize(&mut *self.de).map(Some),
+        }
+    }
+}
+
+struct MapAccess<'a, 'b> {
+    de: &'b mut Deserializer<'a>,
+}
+
+impl<'de, 'a, 'b> de::MapAccess<'de> for MapAccess<'a, 'b> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.de.peek()?.0 {
+            Event::MappingEnd => Ok(None),
+            _ => seed.deserialize(&mut *self.de).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+struct EnumAccess<'a, 'b> {
+    de: &'b mut Deserializer<'a>,
+}
+
+impl<'de, 'a, 'b> de::EnumAccess<'de> for EnumAccess<'a, 'b> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        if let Event::MappingEnd = self.de.peek()?.0 {
+            return Err(de::Error::custom(
+                "expected a mapping with a single entry for an enum",
+            ));
+        }
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, 'b> de::VariantAccess<'de> for EnumAccess<'a, 'b> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::ParserOptions;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: String,
+        port: u16,
+        tags: Vec<String>,
+        timeout: Option<f64>,
+        #[serde(default)]
+        enabled: bool,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[test]
+    fn test_struct() {
+        let s = "
+host: example.com
+port: 8080
+tags: [a, 'b', 1.10]
+timeout: ~
+";
+        let server: Server = from_str(s).unwrap();
+        assert_eq!(
+            server,
+            Server {
+                host: "example.com".to_owned(),
+                port: 8080,
+                tags: vec!["a".to_owned(), "b".to_owned(), "1.10".to_owned()],
+                timeout: None,
+                enabled: false,
+            }
+        );
+
+        let map: BTreeMap<String, i128> =
+            from_str("a: 0x10\nb: 170141183460469231731687303715884105727").unwrap();
+        assert_eq!(map["a"], 16);
+        assert_eq!(map["b"], i128::MAX);
+        let unit: () = from_str("").unwrap();
+        assert_eq!(unit, ());
+        assert_eq!(from_str::<String>("!!binary aGk=").unwrap(), "hi");
+        let err = from_str::<String>("!!binary AP+A").unwrap_err();
+        assert_eq!(err.info(), "invalid value: byte array, expected a string");
+    }
+
+    #[test]
+    fn test_enum() {
+        let shapes: Vec<Shape> = from_str(
+            "
+- Empty
+- Circle: 1.5
+- Point: [1, -2]
+- Rect: {w: 3, h: 4}
+",
+        )
+        .unwrap();
+        assert_eq!(
+            shapes,
+            [
+                Shape::Empty,
+                Shape::Circle(1.5),
+                Shape::Point(1, -2),
+                Shape::Rect { w: 3, h: 4 }
+            ]
+        );
+        let err = from_str::<Shape>("{Circle: 1, Empty: ~}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a mapping with a single entry for an enum at line 1 column 1"
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let s = "
+base: &b
+  host: a
+  port: 1
+  tags: &t [x]
+copy: *b
+tags: *t
+";
+        #[derive(Deserialize)]
+        struct Doc {
+            base: Server,
+            copy: Server,
+            tags: Vec<String>,
+        }
+        let doc: Doc = from_str(s).unwrap();
+        assert_eq!(doc.base, doc.copy);
+        assert_eq!(doc.tags, ["x"]);
+
+        let err = from_str::<Vec<Vec<String>>>("&a [*a]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while deserializing an alias, found a recursive anchor at line 1 column 5"
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        #[derive(Deserialize, Debug)]
+        #[serde(untagged)]
+        enum Node {
+            Leaf(String),
+            List(Vec<Node>),
+        }
+
+        fn leaves(node: &Node) -> usize {
+            match *node {
+                Node::Leaf(ref v) => v.len(),
+                Node::List(ref v) => v.iter().map(leaves).sum(),
+            }
+        }
+
+        let options = LoaderOptions {
+            max_alias_expansion: Some(100),
+            ..LoaderOptions::default()
+        };
+        let mut s = String::from("a0: &a0 [x, x, x, x, x, x, x, x, x]\n");
+        let doc = s.clone() + "b: [*a0, *a0]";
+        let doc: HashMap<String, Node> = from_str_with_options(&doc, &options).unwrap();
+        assert_eq!(leaves(&doc["b"]), 18);
+        for i in 1..10 {
+            s += &format!(
+                "a{0}: &a{0} [*a{1}, *a{1}, *a{1}, *a{1}, *a{1}]\n",
+                i,
+                i - 1
+            );
+        }
+        let err = from_str_with_options::<HashMap<String, Node>>(&s, &options).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::AliasExpansion));
+        // the limit is reached at an alias in `a1` while following `*a1`
+        assert_eq!(err.marker().unwrap().line(), 2);
+        // `from_str` has a limit too
+        let err = from_str::<HashMap<String, Node>>(&s).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::AliasExpansion));
+        // aliases that are skipped aren't expanded
+        let err = from_str_with_options::<HashMap<String, de::IgnoredAny>>(&s, &options);
+        assert!(err.is_ok());
+
+        let options = LoaderOptions {
+            parser: ParserOptions {
+                max_depth: Some(1),
+                ..ParserOptions::default()
+            },
+            ..LoaderOptions::default()
+        };
+        let err = from_str_with_options::<Vec<Vec<u8>>>("[[1]]", &options).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::Depth));
+        assert_eq!(from_str::<Vec<Vec<u8>>>("[[1]]").unwrap(), [[1]]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases: &[(&str, &str)] = &[
+            (
+                "host: a\nport: 70000\ntags: []",
+                "invalid value: integer `70000`, expected u16 at line 2 column 7",
+            ),
+            (
+                "host: a\nport: 1\ntags: [x, [y]]",
+                "invalid type: sequence, expected a string at line 3 column 11",
+            ),
+            (
+                "host: a\nport: 1",
+                "missing field `tags` at line 1 column 1",
+            ),
+            (
+                "host: a\nport: 1\ntags: []\ntimeout: !!float x",
+                "while loading !!float, found an invalid value at line 4 column 18",
+            ),
+            (
+                "host: a\nport: !!int '1x'\ntags: []",
+                "while loading !!int, found an invalid value at line 2 column 13",
+            ),
+            (
+                "host: a\nport: 1\ntags: []\nenabled: yes",
+                "invalid type: string \"yes\", expected a boolean at line 4 column 10",
+            ),
+            (
+                "host: [a",
+                "while parsing a flow sequence, expected ',' or ']' at line 2 column 1",
+            ),
+        ];
+        for &(s, expected) in cases {
+            assert_eq!(from_str::<Server>(s).unwrap_err().to_string(), expected);
+        }
+
+        let err = from_str::<(u8, u8)>("[1, 2, 3]").unwrap_err();
+        assert_eq!(err.marker().unwrap().index(), 0);
+        assert_eq!(from_str::<u8>("--- 1\n--- 2").unwrap_err().marker(), None);
+    }
+}
@@ -0,0 +1,420 @@
+//! Structural differences between two `Yaml` documents.
+
+use crate::base64;
+use crate::emitter::{escape_str, need_quotes};
+use crate::pointer::Pointer;
+use crate::yaml::{Array, Hash, Yaml};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Options for `diff_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Match the elements of sequences of mappings by the value of this key,
+    /// such as `name`, rather than by index. Sequences where an element
+    /// lacks the key, or where two elements share a value, are still
+    /// matched by index.
+    pub array_key: Option<String>,
+}
+
+/// A difference between two documents.
+///
+/// Paths are in the new document, except for removed nodes, whose paths are
+/// in the old one.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    /// A node only in the new document.
+    Added { path: Pointer, value: Yaml },
+    /// A node only in the old document.
+    Removed { path: Pointer, value: Yaml },
+    /// A scalar changed, or a node changed type or tag.
+    Changed { path: Pointer, old: Yaml, new: Yaml },
+}
+
+impl Change {
+    /// The path of the node that changed.
+    pub fn path(&self) -> &Pointer {
+        match *self {
+            Change::Added { ref path, .. }
+            | Change::Removed { ref path, .. }
+            | Change::Changed { ref path, .. } => path,
+        }
+    }
+
+    /// The change as a mapping, such as
+    /// `{op: changed, path: /a/0, old: 1, new: 2}`, for writing with
+    /// `YamlEmitter`.
+    pub fn to_yaml(&self) -> Yaml {
+        let mut hash = Hash::new();
+        let mut insert = |k: &str, v: Yaml| hash.insert(Yaml::String(k.to_owned()), v);
+        let op = match *self {
+            Change::Added { .. } => "added",
+            Change::Removed { .. } => "removed",
+            Change::Changed { .. } => "changed",
+        };
+        insert("op", Yaml::String(op.to_owned()));
+        insert("path", Yaml::String(self.path().to_string()));
+        match *self {
+            Change::Added { ref value, .. } | Change::Removed { ref value, .. } => {
+                insert("value", value.clone());
+            }
+            Change::Changed {
+                ref old, ref new, ..
+            } => {
+                insert("old", old.clone());
+                insert("new", new.clone());
+            }
+        }
+        Yaml::Hash(hash)
+    }
+}
+
+impl fmt::Display for Change {
+    /// Writes the change on one line, such as `~ /a/0: 1 -> 2`, with
+    /// values in flow style.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Added {
+                ref path,
+                ref value,
+            } => {
+                write!(f, "+ {}: ", path)?;
+                write_flow(f, value)
+            }
+            Change::Removed {
+                ref path,
+                ref value,
+            } => {
+                write!(f, "- {}: ", path)?;
+                write_flow(f, value)
+            }
+            Change::Changed {
+                ref path,
+                ref old,
+                ref new,
+            } => {
+                write!(f, "~ {}: ", path)?;
+                write_flow(f, old)?;
+                f.write_str(" -> ")?;
+                write_flow(f, new)
+            }
+        }
+    }
+}
+
+// Writes a node on one line in flow style.
+pub(crate) fn write_flow(f: &mut dyn fmt::Write, node: &Yaml) -> fmt::Result {
+    match *node {
+        Yaml::String(ref v) if need_quotes(v) => escape_str(f, v),
+        Yaml::String(ref v) | Yaml::Real(ref v) | Yaml::Timestamp(ref v) => f.write_str(v),
+        Yaml::Integer(v) => write!(f, "{}", v),
+        Yaml::BigInteger(v) => write!(f, "{}", v),
+        Yaml::Number(ref v) => write!(f, "{}", v),
+        Yaml::Boolean(v) => write!(f, "{}", v),
+        Yaml::Binary(ref v) => write!(f, "!!binary {}", base64::encode(v)),
+        Yaml::Tagged(ref tag, ref v) => {
+            write!(f, "{} ", tag)?;
+            write_flow(f, v)
+        }
+        Yaml::Array(ref v) => {
+            f.write_str("[")?;
+            for (i, x) in v.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write_flow(f, x)?;
+            }
+            f.write_str("]")
+        }
+        Yaml::Hash(ref h) => {
+            f.write_str("{")?;
+            for (i, (k, v)) in h.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write_flow(f, k)?;
+                f.write_str(": ")?;
+                write_flow(f, v)?;
+            }
+            f.write_str("}")
+        }
+        Yaml::Null | Yaml::BadValue | Yaml::Alias(_) => f.write_str("~"),
+    }
+}
+
+/// The changes that turn `old` into `new`, matching sequence elements by
+/// index.
+///
+/// ```
+/// use yaml_rust::diff::diff;
+/// use yaml_rust::YamlLoader;
+///
+/// let old = &YamlLoader::load_from_str("{a: 1, b: [x, y]}").unwrap()[0];
+/// let new = &YamlLoader::load_from_str("{a: 2, b: [x], c: true}").unwrap()[0];
+/// let changes: Vec<_> = diff(old, new).iter().map(|c| c.to_string()).collect();
+/// assert_eq!(changes, ["~ /a: 1 -> 2", "- /b/1: y", "+ /c: true"]);
+/// ```
+pub fn diff(old: &Yaml, new: &Yaml) -> Vec<Change> {
+    diff_with_options(old, new, &DiffOptions::default())
+}
+
+/// The changes that turn `old` into `new`.
+///
+/// Mapping entries are reported in the order of `old`, followed by the
+/// entries added in `new`. Moving an entry within a mapping, or an element
+/// within a sequence matched by key, is not a change.
+pub fn diff_with_options(old: &Yaml, new: &Yaml, options: &DiffOptions) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_node(&Pointer::root(), old, new, options, &mut changes);
+    changes
+}
+
+/// The changes rendered one per line, for reading.
+pub fn render(changes: &[Change]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        out.push_str(&change.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+fn diff_node(path: &Pointer, old: &Yaml, new: &Yaml, options: &DiffOptions, out: &mut Vec<Change>) {
+    match (old, new) {
+        (Yaml::Hash(old), Yaml::Hash(new)) => diff_hash(path, old, new, options, out),
+        (Yaml::Array(old), Yaml::Array(new)) => {
+            let keyed = options
+                .array_key
+                .as_ref()
+                .and_then(|key| Some((keys(old, key)?, keys(new, key)?)));
+            match keyed {
+                Some((old_keys, new_keys)) => {
+                    diff_keyed(path, old, new, &old_keys, &new_keys, options, out)
+                }
+                None => diff_array(path, old, new, options, out),
+            }
+        }
+        (Yaml::Tagged(old_tag, old), Yaml::Tagged(new_tag, new)) if old_tag == new_tag => {
+            diff_node(path, old, new, options, out)
+        }
+        (old, new) if old == new => {}
+        (old, new) => out.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+fn diff_hash(path: &Pointer, old: &Hash, new: &Hash, options: &DiffOptions, out: &mut Vec<Change>) {
+    for (k, v) in old {
+        match new.get(k) {
+            Some(new_v) => diff_node(&path.join(k.clone()), v, new_v, options, out),
+            None => out.push(Change::Removed {
+                path: path.join(k.clone()),
+                value: v.clone(),
+            }),
+        }
+    }
+    for (k, v) in new {
+        if !old.contains_key(k) {
+            out.push(Change::Added {
+                path: path.join(k.clone()),
+                value: v.clone(),
+            });
+        }
+    }
+}
+
+fn diff_array(
+    path: &Pointer,
+    old: &Array,
+    new: &Array,
+    options: &DiffOptions,
+    out: &mut Vec<Change>,
+) {
+    for (i, (old, new)) in old.iter().zip(new).enumerate() {
+        diff_node(&path.join(Yaml::Integer(i as i64)), old, new, options, out);
+    }
+    for (i, v) in old.iter().enumerate().skip(new.len()) {
+        out.push(Change::Removed {
+            path: path.join(Yaml::Integer(i as i64)),
+            value: v.clone(),
+        });
+    }
+    for (i, v) in new.iter().enumerate().skip(old.len()) {
+        out.push(Change::Added {
+            path: path.join(Yaml::Integer(i as i64)),
+            value: v.clone(),
+        });
+    }
+}
+
+// The value of `key` in each element, if every element is a mapping with a
+// distinct value for it.
+fn keys<'a>(array: &'a Array, key: &str) -> Option<Vec<&'a Yaml>> {
+    let key = Yaml::String(key.to_owned());
+    let mut seen = HashSet::new();
+    let mut keys = Vec::with_capacity(array.len());
+    for v in array {
+        let k = v.as_hash()?.get(&key)?;
+        if !seen.insert(k) {
+            return None;
+        }
+        keys.push(k);
+    }
+    Some(keys)
+}
+
+fn diff_keyed(
+    path: &Pointer,
+    old: &Array,
+    new: &Array,
+    old_keys: &[&Yaml],
+    new_keys: &[&Yaml],
+    options: &DiffOptions,
+    out: &mut Vec<Change>,
+) {
+    for (i, v) in old.iter().enumerate() {
+        match new_keys.iter().position(|k| *k == old_keys[i]) {
+            Some(j) => diff_node(
+                &path.join(Yaml::Integer(j as i64)),
+                v,
+                &new[j],
+                options,
+                out,
+            ),
+            None => out.push(Change::Removed {
+                path: path.join(Yaml::Integer(i as i64)),
+                value: v.clone(),
+            }),
+        }
+    }
+    for (j, v) in new.iter().enumerate() {
+        if !old_keys.contains(&new_keys[j]) {
+            out.push(Change::Added {
+                path: path.join(Yaml::Integer(j as i64)),
+                value: v.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emitter::YamlEmitter;
+    use crate::yaml::YamlLoader;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = load(
+            "
+name: app
+replicas: 2
+ports: [80, 443]
+env: {A: '1', B: x}
+tagged: !t {k: v}
+retag: !a 1
+",
+        );
+        let new = load(
+            "
+name: app
+replicas: 3
+ports: [80]
+env: {B: x, A: 1, C: [a, 'b c']}
+tagged: !t {k: w}
+retag: !b 1
+extra: ~
+",
+        );
+        assert_eq!(
+            render(&diff(&old, &new)),
+            "~ /replicas: 2 -> 3
+- /ports/1: 443
+~ /env/A: \"1\" -> 1
++ /env/C: [a, b c]
+~ /tagged/k: v -> w
+~ /retag: !a \"1\" -> !b \"1\"
++ /extra: ~
+"
+        );
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&load("[1]"), &load("{a: 1}")),
+            [Change::Changed {
+                path: Pointer::root(),
+                old: load("[1]"),
+                new: load("{a: 1}"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_array_key() {
+        let old = load(
+            "
+containers:
+  - {name: web, image: nginx:1}
+  - {name: log, image: fluentd}
+  - {name: old, image: x}
+volumes: [a, b]
+",
+        );
+        let new = load(
+            "
+containers:
+  - {name: sidecar, image: envoy}
+  - {name: log, image: fluentd}
+  - {name: web, image: nginx:2}
+volumes: [b, a]
+",
+        );
+        let options = DiffOptions {
+            array_key: Some("name".to_owned()),
+        };
+        let changes = diff_with_options(&old, &new, &options);
+        assert_eq!(
+            render(&changes),
+            "~ /containers/2/image: \"nginx:1\" -> \"nginx:2\"
+- /containers/2: {name: old, image: x}
++ /containers/0: {name: sidecar, image: envoy}
+~ /volumes/0: a -> b
+~ /volumes/1: b -> a
+"
+        );
+
+        // a duplicate key falls back to matching by index
+        let old = load("[{name: a, v: 1}, {name: a, v: 2}]");
+        let new = load("[{name: a, v: 2}, {name: a, v: 2}]");
+        let changes = diff_with_options(&old, &new, &options);
+        assert_eq!(render(&changes), "~ /0/v: 1 -> 2\n");
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        let changes = diff(&load("{a: 1, b: 2}"), &load("{a: 3, c: 4}"));
+        let list = Yaml::Array(changes.iter().map(Change::to_yaml).collect());
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&list).unwrap();
+        assert_eq!(
+            out,
+            "---
+- op: changed
+  path: /a
+  old: 1
+  new: 3
+- op: removed
+  path: /b
+  value: 2
+- op: added
+  path: /c
+  value: 4"
+        );
+    }
+}
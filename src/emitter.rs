@@ -1,12 +1,25 @@
+use std::cmp;
+use std::collections::HashSet;
 use std::convert::From;
 use std::error::Error;
 use std::fmt::{self, Display};
-use crate::yaml::{Hash, Yaml};
+use std::str;
+use crate::base64;
+use crate::timestamp::Timestamp;
+use crate::yaml::{parse_f64, Hash, Yaml};
 
 #[derive(Copy, Clone, Debug)]
 pub enum EmitError {
     FmtError(fmt::Error),
     BadHashmapKey,
+    /// An infinite or NaN real, which `JsonEmitter` cannot write.
+    NonFiniteReal,
+    /// A `Yaml::BadValue` or `Yaml::Alias`, which `JsonEmitter` cannot
+    /// write.
+    BadValue,
+    /// Two keys of a mapping that `JsonEmitter` writes as the same string,
+    /// such as `1` and `'1'`.
+    DuplicateKey,
 }
 
 impl Error for EmitError {
@@ -20,6 +33,9 @@ impl Display for EmitError {
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
             EmitError::BadHashmapKey => formatter.write_str("bad hashmap key"),
+            EmitError::NonFiniteReal => formatter.write_str("non-finite real"),
+            EmitError::BadValue => formatter.write_str("bad value"),
+            EmitError::DuplicateKey => formatter.write_str("duplicate key"),
         }
     }
 }
@@ -34,6 +50,7 @@ pub struct YamlEmitter<'a> {
     writer: &'a mut dyn fmt::Write,
     best_indent: usize,
     compact: bool,
+    quote_timestamps: bool,
 
     level: isize,
 }
@@ -41,7 +58,7 @@ pub struct YamlEmitter<'a> {
 pub type EmitResult = Result<(), EmitError>;
 
 // from serialize::json
-fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+pub(crate) fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
     wr.write_str("\"")?;
 
     let mut start = 0;
@@ -109,6 +126,7 @@ impl<'a> YamlEmitter<'a> {
             writer,
             best_indent: 2,
             compact: true,
+            quote_timestamps: false,
             level: -1,
         }
     }
@@ -119,8 +137,7 @@ impl<'a> YamlEmitter<'a> {
     /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
     ///
     /// In this form, blocks cannot have any properties (such as anchors
-    /// or tags), which should be OK, because this emitter doesn't
-    /// (currently) emit those anyways.
+    /// or tags), so tagged collections always start on a new line.
     pub fn compact(&mut self, compact: bool) {
         self.compact = compact;
     }
@@ -130,6 +147,13 @@ impl<'a> YamlEmitter<'a> {
         self.compact
     }
 
+    /// Quote strings that look like timestamps, such as `2014-12-31`, so
+    /// that a loader with `LoaderOptions::timestamps` set reads them back as
+    /// strings. Off by default.
+    pub fn quote_timestamps(&mut self, quote_timestamps: bool) {
+        self.quote_timestamps = quote_timestamps;
+    }
+
     pub fn dump(&mut self, doc: &Yaml) -> EmitResult {
         // write DocumentStart
         writeln!(self.writer, "---")?;
@@ -154,7 +178,7 @@ impl<'a> YamlEmitter<'a> {
             Yaml::Array(ref v) => self.emit_array(v),
             Yaml::Hash(ref h) => self.emit_hash(h),
             Yaml::String(ref v) => {
-                if need_quotes(v) {
+                if need_quotes(v) || (self.quote_timestamps && Timestamp::parse(v).is_some()) {
                     escape_str(self.writer, v)?;
                 } else {
                     write!(self.writer, "{}", v)?;
@@ -173,7 +197,15 @@ impl<'a> YamlEmitter<'a> {
                 write!(self.writer, "{}", v)?;
                 Ok(())
             }
-            Yaml::Real(ref v) => {
+            Yaml::BigInteger(v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            Yaml::Number(ref v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            Yaml::Real(ref v) | Yaml::Timestamp(ref v) => {
                 write!(self.writer, "{}", v)?;
                 Ok(())
             }
@@ -181,11 +213,50 @@ impl<'a> YamlEmitter<'a> {
                 write!(self.writer, "~")?;
                 Ok(())
             }
+            Yaml::Binary(ref v) => self.emit_binary(v),
+            Yaml::Tagged(ref tag, ref v) => {
+                write!(self.writer, "{}", tag)?;
+                match **v {
+                    // an ordered map is written as a sequence of pairs
+                    Yaml::Hash(ref h) if tag == "!!omap" => {
+                        let pairs = h
+                            .iter()
+                            .map(|(k, v)| {
+                                let mut pair = Hash::new();
+                                pair.insert(k.clone(), v.clone());
+                                Yaml::Hash(pair)
+                            })
+                            .collect();
+                        self.emit_val(false, &Yaml::Array(pairs))
+                    }
+                    _ => self.emit_val(false, v),
+                }
+            }
             // XXX(chenyh) Alias
             _ => Ok(()),
         }
     }
 
+    // Write base64 wrapped at 76 columns, as a literal block scalar.
+    fn emit_binary(&mut self, v: &[u8]) -> EmitResult {
+        let text = base64::encode(v);
+        if text.is_empty() {
+            self.writer.write_str("!!binary \"\"")?;
+            return Ok(());
+        }
+        self.writer.write_str("!!binary |")?;
+        // the content must be indented, even at the top level
+        let level = self.level;
+        self.level = cmp::max(level + 1, 1);
+        for line in text.as_bytes().chunks(76) {
+            writeln!(self.writer)?;
+            self.write_indent()?;
+            self.writer.write_str(str::from_utf8(line).unwrap())?;
+        }
+        self.level = level;
+        Ok(())
+    }
+
     fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
         if v.is_empty() {
             write!(self.writer, "[]")?;
@@ -211,7 +282,11 @@ impl<'a> YamlEmitter<'a> {
             self.level += 1;
             for (cnt, (k, v)) in h.iter().enumerate() {
                 let complex_key = match *k {
-                    Yaml::Hash(_) | Yaml::Array(_) => true,
+                    Yaml::Hash(_) | Yaml::Array(_) | Yaml::Binary(_) => true,
+                    Yaml::Tagged(_, ref k) => match **k {
+                        Yaml::Hash(_) | Yaml::Array(_) => true,
+                        _ => false,
+                    },
                     _ => false,
                 };
                 if cnt > 0 {
@@ -272,6 +347,262 @@ impl<'a> YamlEmitter<'a> {
     }
 }
 
+/// What `JsonEmitter` writes for a node that JSON cannot represent.
+#[derive(Copy, Clone, PartialEq, Debug, Eq)]
+pub enum JsonFallback {
+    /// Fail with an `EmitError`.
+    Error,
+    /// Write `null`.
+    Null,
+}
+
+/// Writes a `Yaml` document as JSON.
+///
+/// Keys that are scalars are written as strings, such as `"1"` for the
+/// integer `1` and `"null"` for a null, and a collection or binary key is
+/// `EmitError::BadHashmapKey`. Keys of a mapping that are written as the
+/// same string are `EmitError::DuplicateKey`. Tags are dropped, timestamps are written as
+/// strings and binary data as base64 strings. Infinite and NaN reals, and
+/// `Yaml::BadValue`, are errors unless a `JsonFallback` is set.
+///
+/// ```
+/// use yaml_rust::{JsonEmitter, YamlLoader};
+///
+/// let docs = YamlLoader::load_from_str("{a: [1, 2.5], 3: ~}").unwrap();
+/// let mut out = String::new();
+/// JsonEmitter::new(&mut out).dump(&docs[0]).unwrap();
+/// assert_eq!(out, r#"{"a":[1,2.5],"3":null}"#);
+/// ```
+pub struct JsonEmitter<'a> {
+    writer: &'a mut dyn fmt::Write,
+    pretty: bool,
+    non_finite: JsonFallback,
+    bad_value: JsonFallback,
+
+    level: usize,
+}
+
+impl<'a> JsonEmitter<'a> {
+    pub fn new(writer: &'a mut dyn fmt::Write) -> JsonEmitter<'a> {
+        JsonEmitter {
+            writer,
+            pretty: false,
+            non_finite: JsonFallback::Error,
+            bad_value: JsonFallback::Error,
+            level: 0,
+        }
+    }
+
+    /// Write every element of a non-empty collection on its own line,
+    /// indented by two spaces per level. Off by default.
+    pub fn pretty(&mut self, pretty: bool) {
+        self.pretty = pretty;
+    }
+
+    /// Determine if this emitter writes pretty JSON.
+    pub fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    /// What to write for `.inf`, `-.inf` and `.nan`.
+    pub fn non_finite(&mut self, fallback: JsonFallback) {
+        self.non_finite = fallback;
+    }
+
+    /// What to write for `Yaml::BadValue`, or for a `Yaml::Alias`, which a
+    /// loaded document never contains.
+    pub fn bad_value(&mut self, fallback: JsonFallback) {
+        self.bad_value = fallback;
+    }
+
+    /// Write `doc`, without a trailing newline.
+    pub fn dump(&mut self, doc: &Yaml) -> EmitResult {
+        self.level = 0;
+        self.emit_node(doc)
+    }
+
+    fn write_newline(&mut self) -> EmitResult {
+        if self.pretty {
+            writeln!(self.writer)?;
+            for _ in 0..self.level {
+                self.writer.write_str("  ")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fallback(&mut self, fallback: JsonFallback, err: EmitError) -> EmitResult {
+        match fallback {
+            JsonFallback::Error => Err(err),
+            JsonFallback::Null => {
+                self.writer.write_str("null")?;
+                Ok(())
+            }
+        }
+    }
+
+    fn emit_node(&mut self, node: &Yaml) -> EmitResult {
+        match *node {
+            Yaml::Array(ref v) => self.emit_array(v),
+            Yaml::Hash(ref h) => self.emit_hash(h),
+            Yaml::String(ref v) | Yaml::Timestamp(ref v) => {
+                escape_str(self.writer, v)?;
+                Ok(())
+            }
+            Yaml::Boolean(v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            Yaml::Integer(v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            Yaml::BigInteger(v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            Yaml::Number(ref v) => match v.as_i128() {
+                // the literal may be hexadecimal, or have underscores
+                Some(i) => {
+                    write!(self.writer, "{}", i)?;
+                    Ok(())
+                }
+                None => self.emit_real(v.literal(), v.as_f64()),
+            },
+            Yaml::Real(ref v) => match parse_f64(v) {
+                Some(f) => self.emit_real(v, f),
+                None => self.fallback(self.bad_value, EmitError::BadValue),
+            },
+            Yaml::Null => {
+                self.writer.write_str("null")?;
+                Ok(())
+            }
+            Yaml::Binary(ref v) => {
+                escape_str(self.writer, &base64::encode(v))?;
+                Ok(())
+            }
+            Yaml::Tagged(_, ref v) => self.emit_node(v),
+            Yaml::BadValue | Yaml::Alias(_) => self.fallback(self.bad_value, EmitError::BadValue),
+        }
+    }
+
+    // Keeps the literal if it is a JSON number, so `2.50` stays `2.50`.
+    fn emit_real(&mut self, literal: &str, v: f64) -> EmitResult {
+        if !v.is_finite() {
+            return self.fallback(self.non_finite, EmitError::NonFiniteReal);
+        }
+        if is_json_number(literal) {
+            self.writer.write_str(literal)?;
+        } else {
+            write!(self.writer, "{:?}", v)?;
+        }
+        Ok(())
+    }
+
+    fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
+        if v.is_empty() {
+            self.writer.write_str("[]")?;
+            return Ok(());
+        }
+        self.writer.write_str("[")?;
+        self.level += 1;
+        for (cnt, x) in v.iter().enumerate() {
+            if cnt > 0 {
+                self.writer.write_str(",")?;
+            }
+            self.write_newline()?;
+            self.emit_node(x)?;
+        }
+        self.level -= 1;
+        self.write_newline()?;
+        self.writer.write_str("]")?;
+        Ok(())
+    }
+
+    fn emit_hash(&mut self, h: &Hash) -> EmitResult {
+        if h.is_empty() {
+            self.writer.write_str("{}")?;
+            return Ok(());
+        }
+        self.writer.write_str("{")?;
+        self.level += 1;
+        let mut keys = HashSet::new();
+        for (cnt, (k, v)) in h.iter().enumerate() {
+            if cnt > 0 {
+                self.writer.write_str(",")?;
+            }
+            self.write_newline()?;
+            let key = self.key_text(k)?;
+            if keys.contains(&key) {
+                return Err(EmitError::DuplicateKey);
+            }
+            escape_str(self.writer, &key)?;
+            keys.insert(key);
+            self.writer
+                .write_str(if self.pretty { ": " } else { ":" })?;
+            self.emit_node(v)?;
+        }
+        self.level -= 1;
+        self.write_newline()?;
+        self.writer.write_str("}")?;
+        Ok(())
+    }
+
+    // The string a key is written as.
+    fn key_text(&self, k: &Yaml) -> Result<String, EmitError> {
+        match *k {
+            Yaml::String(ref v) | Yaml::Timestamp(ref v) => Ok(v.clone()),
+            Yaml::Tagged(_, ref k) => self.key_text(k),
+            Yaml::Array(_) | Yaml::Hash(_) | Yaml::Binary(_) => Err(EmitError::BadHashmapKey),
+            _ => {
+                let mut text = String::new();
+                {
+                    let mut emitter = JsonEmitter::new(&mut text);
+                    emitter.non_finite(self.non_finite);
+                    emitter.bad_value(self.bad_value);
+                    emitter.emit_node(k)?;
+                }
+                Ok(text)
+            }
+        }
+    }
+}
+
+// `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`
+fn is_json_number(v: &str) -> bool {
+    fn digits(b: &[u8]) -> usize {
+        b.iter().take_while(|b| b.is_ascii_digit()).count()
+    }
+    let mut b = v.as_bytes();
+    if b.first() == Some(&b'-') {
+        b = &b[1..];
+    }
+    let n = digits(b);
+    if n == 0 || (n > 1 && b[0] == b'0') {
+        return false;
+    }
+    b = &b[n..];
+    if b.first() == Some(&b'.') {
+        let n = digits(&b[1..]);
+        if n == 0 {
+            return false;
+        }
+        b = &b[n + 1..];
+    }
+    if let Some(&b'e') | Some(&b'E') = b.first() {
+        b = &b[1..];
+        if let Some(&b'+') | Some(&b'-') = b.first() {
+            b = &b[1..];
+        }
+        let n = digits(b);
+        if n == 0 {
+            return false;
+        }
+        b = &b[n..];
+    }
+    b.is_empty()
+}
+
 /// Check if the string requires quoting.
 /// Strings starting with any of the following characters must be quoted.
 /// :, &, *, ?, |, -, <, >, =, !, %, @
@@ -286,7 +617,7 @@ impl<'a> YamlEmitter<'a> {
 /// * When the string is null or ~ (otherwise, it would be considered as a null value);
 /// * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
 /// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
-fn need_quotes(string: &str) -> bool {
+pub(crate) fn need_quotes(string: &str) -> bool {
     fn need_quotes_spaces(string: &str) -> bool {
         string.starts_with(' ') || string.ends_with(' ')
     }
@@ -337,7 +668,8 @@ fn need_quotes(string: &str) -> bool {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::YamlLoader;
+    use crate::yaml::LoaderOptions;
+    use crate::{Schema, YamlLoader};
 
     #[test]
     fn test_emit_simple() {
@@ -632,4 +964,262 @@ a:
         assert_eq!(s, writer);
     }
 
+    #[test]
+    fn test_tagged() {
+        let s = r#"---
+password: !secret hunter2
+port: !secret "8080"
+db: !ref
+  host: !env DB_HOST
+list: !pairs
+  - a
+  - !<tag:example.com,2000:x> b
+empty: !set {}
+? !point
+  - 1
+  - 2
+: origin"#;
+
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc = &docs[0];
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_collection_types() {
+        let s = r#"---
+set: !!set
+  a: ~
+  b: ~
+omap: !!omap
+  - z: 1
+  - a:
+      - x
+pairs: !!pairs
+  - a: 1
+  - a: 2
+empty: !!omap []"#;
+
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc = &docs[0];
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let s = r#"---
+- 18446744073709551615
+- -170141183460469231731687303715884105728
+- "18446744073709551615""#;
+
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc = &docs[0];
+        assert_eq!(doc[0].as_u64(), Some(u64::MAX));
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(doc).unwrap();
+        }
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let s = r#"---
+mode: 0o755
+mask: 0xFF
+ratio: 1.50
+big: 18446744073709551615
+inf: -.inf"#;
+
+        let options = LoaderOptions {
+            numbers: true,
+            ..LoaderOptions::default()
+        };
+        let docs = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        let doc = &docs[0];
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(doc).unwrap();
+        }
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_binary() {
+        let s = r#"---
+empty: !!binary ""
+short: !!binary |
+  aGVsbG8=
+long:
+  - !!binary |
+    AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4
+    OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5fYGFiY2Q="#;
+
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc = &docs[0];
+        assert_eq!(doc["short"].as_binary().unwrap(), b"hello");
+        let bytes: Vec<u8> = (0..101).collect();
+        assert_eq!(doc["long"][0].as_binary(), Some(&bytes));
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(doc).unwrap();
+        }
+        assert_eq!(s, writer);
+
+        let mut writer = String::new();
+        YamlEmitter::new(&mut writer)
+            .dump(&Yaml::Binary(bytes))
+            .unwrap();
+        assert_eq!(
+            YamlLoader::load_from_str(&writer).unwrap()[0],
+            doc["long"][0]
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let s = r#"---
+date: 2002-12-14
+time: 2001-12-14 21:59:43.10 -5
+zulu: 2001-12-15T02:59:43.1Z
+string: "2002-12-14""#;
+
+        let options = LoaderOptions {
+            timestamps: true,
+            ..LoaderOptions::default()
+        };
+        let docs = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        let doc = &docs[0];
+        assert_eq!(doc["date"], Yaml::Timestamp("2002-12-14".to_owned()));
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.quote_timestamps(true);
+            emitter.dump(doc).unwrap();
+        }
+        let docs_new = YamlLoader::<Yaml>::load_with_options(&writer, &options).unwrap();
+        assert_eq!(doc, &docs_new[0]);
+        assert!(writer.contains("\ndate: 2002-12-14\n"));
+    }
+
+    fn to_json(doc: &Yaml, pretty: bool) -> Result<String, EmitError> {
+        let mut writer = String::new();
+        {
+            let mut emitter = JsonEmitter::new(&mut writer);
+            emitter.pretty(pretty);
+            emitter.dump(doc)?;
+        }
+        Ok(writer)
+    }
+
+    #[test]
+    fn test_json() {
+        let s = r#"
+str: "a \"b\"\n\t\x01 é"
+int: [0, -12, 0x1F, 1_000, 170141183460469231731687303715884105727]
+real: [2.50, -1.5e+3, .5, +1.0, 1_000.5, 1.]
+other: [true, ~, !!binary AQID, !foo {x: 1}, []]
+"#;
+        let options = LoaderOptions {
+            schema: Some(Schema::Yaml11),
+            numbers: true,
+            ..Default::default()
+        };
+        let docs = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        assert_eq!(
+            to_json(&docs[0], false).unwrap(),
+            r#"{"str":"a \"b\"\n\t\u0001 é","int":[0,-12,31,1000,170141183460469231731687303715884105727],"real":[2.50,-1.5e+3,0.5,1.0,1000.5,1.0],"other":[true,null,"AQID",{"x":1},[]]}"#
+        );
+    }
+
+    #[test]
+    fn test_json_pretty() {
+        let docs =
+            YamlLoader::load_from_str("{a: [1, {b: 2}], c: {}, 1: x, true: y, ~: z}").unwrap();
+        assert_eq!(
+            to_json(&docs[0], true).unwrap(),
+            r#"{
+  "a": [
+    1,
+    {
+      "b": 2
+    }
+  ],
+  "c": {},
+  "1": "x",
+  "true": "y",
+  "null": "z"
+}"#
+        );
+        assert_eq!(to_json(&Yaml::Array(vec![]), true).unwrap(), "[]");
+        assert_eq!(to_json(&Yaml::Integer(1), true).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let docs = YamlLoader::load_from_str("[[1]: x]").unwrap();
+        assert!(matches!(
+            to_json(&docs[0], false),
+            Err(EmitError::BadHashmapKey)
+        ));
+
+        let doc = &YamlLoader::load_from_str("{.inf: [-.inf, .nan, !!int x]}").unwrap()[0];
+        assert!(matches!(to_json(doc, false), Err(EmitError::NonFiniteReal)));
+        let mut writer = String::new();
+        {
+            let mut emitter = JsonEmitter::new(&mut writer);
+            emitter.non_finite(JsonFallback::Null);
+            let err = emitter.dump(doc).unwrap_err();
+            assert_eq!(err.to_string(), "bad value");
+        }
+        writer.clear();
+        {
+            let mut emitter = JsonEmitter::new(&mut writer);
+            emitter.non_finite(JsonFallback::Null);
+            emitter.bad_value(JsonFallback::Null);
+            emitter.dump(doc).unwrap();
+        }
+        assert_eq!(writer, r#"{"null":[null,null,null]}"#);
+
+        let docs = YamlLoader::load_from_str("[{1: a, '1': b}, {.inf: a, .nan: b}]").unwrap();
+        assert!(matches!(
+            to_json(&docs[0][0], false),
+            Err(EmitError::DuplicateKey)
+        ));
+        writer.clear();
+        {
+            let mut emitter = JsonEmitter::new(&mut writer);
+            emitter.non_finite(JsonFallback::Null);
+            let err = emitter.dump(&docs[0][1]).unwrap_err();
+            assert_eq!(err.to_string(), "duplicate key");
+        }
+        // keys of different mappings don't collide
+        let docs = YamlLoader::load_from_str("{1: {1: a}, '2': {2: b}}").unwrap();
+        assert_eq!(
+            to_json(&docs[0], false).unwrap(),
+            r#"{"1":{"1":"a"},"2":{"2":"b"}}"#
+        );
+    }
+
 }
@@ -0,0 +1,201 @@
+// Detection and decoding of the encodings a YAML stream may use, see
+// https://yaml.org/spec/1.2/spec.html#id2771184
+
+use crate::scanner::{Marker, ScanError, TEncoding};
+use std::char;
+use std::str;
+
+/// Detect the encoding of a stream from its byte order mark, or else from
+/// the null bytes around its first character, which YAML requires to be
+/// ASCII.
+pub fn detect(bytes: &[u8]) -> TEncoding {
+    let b = |i: usize| bytes.get(i).cloned();
+    match (b(0), b(1), b(2), b(3)) {
+        (Some(0), Some(0), Some(0xFE), Some(0xFF)) | (Some(0), Some(0), Some(0), Some(_)) => {
+            TEncoding::Utf32Be
+        }
+        (Some(0xFF), Some(0xFE), Some(0), Some(0)) | (Some(_), Some(0), Some(0), Some(0)) => {
+            TEncoding::Utf32Le
+        }
+        (Some(0xFE), Some(0xFF), _, _) | (Some(0), Some(_), _, _) => TEncoding::Utf16Be,
+        (Some(0xFF), Some(0xFE), _, _) | (Some(_), Some(0), _, _) => TEncoding::Utf16Le,
+        _ => TEncoding::Utf8,
+    }
+}
+
+/// Decode a stream in the encoding `detect` finds, dropping the byte order
+/// mark if there is one.
+pub fn decode(bytes: &[u8]) -> Result<(String, TEncoding), ScanError> {
+    let encoding = detect(bytes);
+    let mut text = match encoding {
+        TEncoding::Utf8 => match str::from_utf8(bytes) {
+            Ok(s) => s.to_owned(),
+            Err(e) => {
+                let valid = str::from_utf8(&bytes[..e.valid_up_to()]).unwrap();
+                return Err(invalid(encoding, valid, e.valid_up_to()));
+            }
+        },
+        TEncoding::Utf16Le | TEncoding::Utf16Be => decode_utf16(bytes, encoding)?,
+        TEncoding::Utf32Le | TEncoding::Utf32Be => decode_utf32(bytes, encoding)?,
+    };
+    if text.starts_with('\u{feff}') {
+        text.remove(0);
+    }
+    Ok((text, encoding))
+}
+
+fn decode_utf16(bytes: &[u8], encoding: TEncoding) -> Result<String, ScanError> {
+    let units = bytes.chunks_exact(2).map(|b| match encoding {
+        TEncoding::Utf16Be => u16::from_be_bytes([b[0], b[1]]),
+        _ => u16::from_le_bytes([b[0], b[1]]),
+    });
+    let mut text = String::with_capacity(bytes.len() / 2);
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => text.push(c),
+            Err(_) => {
+                let offset = 2 * text.encode_utf16().count();
+                return Err(invalid(encoding, &text, offset));
+            }
+        }
+    }
+    if bytes.len() % 2 != 0 {
+        return Err(invalid(encoding, &text, bytes.len() - 1));
+    }
+    Ok(text)
+}
+
+fn decode_utf32(bytes: &[u8], encoding: TEncoding) -> Result<String, ScanError> {
+    let mut text = String::with_capacity(bytes.len() / 4);
+    for (i, b) in bytes.chunks(4).enumerate() {
+        let c = match *b {
+            [b0, b1, b2, b3] if encoding == TEncoding::Utf32Be => {
+                char::from_u32(u32::from_be_bytes([b0, b1, b2, b3]))
+            }
+            [b0, b1, b2, b3] => char::from_u32(u32::from_le_bytes([b0, b1, b2, b3])),
+            _ => None,
+        };
+        match c {
+            Some(c) => text.push(c),
+            None => return Err(invalid(encoding, &text, 4 * i)),
+        }
+    }
+    Ok(text)
+}
+
+// The error for an invalid byte sequence at `offset`, located after the
+// text decoded so far.
+fn invalid(encoding: TEncoding, decoded: &str, offset: usize) -> ScanError {
+    let decoded = if decoded.starts_with('\u{feff}') {
+        &decoded['\u{feff}'.len_utf8()..]
+    } else {
+        decoded
+    };
+    let line = decoded.matches('\n').count() + 1;
+    let col = decoded.rsplit('\n').next().unwrap().chars().count();
+    let mark = Marker::new(decoded.chars().count(), line, col);
+    let name = match encoding {
+        TEncoding::Utf8 => "UTF-8",
+        TEncoding::Utf16Le => "UTF-16LE",
+        TEncoding::Utf16Be => "UTF-16BE",
+        TEncoding::Utf32Le => "UTF-32LE",
+        TEncoding::Utf32Be => "UTF-32BE",
+    };
+    ScanError::new(
+        mark,
+        &format!("invalid {} byte sequence at byte offset {}", name, offset),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn utf16(s: &str, big_endian: bool) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    fn utf32(s: &str, big_endian: bool) -> Vec<u8> {
+        s.chars()
+            .flat_map(|c| {
+                if big_endian {
+                    u32::from(c).to_be_bytes()
+                } else {
+                    u32::from(c).to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let s = "a: é😀";
+        let cases = [
+            (s.as_bytes().to_vec(), TEncoding::Utf8),
+            (utf16(s, false), TEncoding::Utf16Le),
+            (utf16(s, true), TEncoding::Utf16Be),
+            (utf32(s, false), TEncoding::Utf32Le),
+            (utf32(s, true), TEncoding::Utf32Be),
+        ];
+        for (bytes, encoding) in &cases {
+            assert_eq!(detect(bytes), *encoding);
+            assert_eq!(decode(bytes).unwrap(), (s.to_owned(), *encoding));
+        }
+
+        // with a byte order mark, the first character needn't be ASCII
+        let s = "\u{feff}é";
+        let cases = [
+            (s.as_bytes().to_vec(), TEncoding::Utf8),
+            (utf16(s, false), TEncoding::Utf16Le),
+            (utf16(s, true), TEncoding::Utf16Be),
+            (utf32(s, false), TEncoding::Utf32Le),
+            (utf32(s, true), TEncoding::Utf32Be),
+        ];
+        for (bytes, encoding) in &cases {
+            assert_eq!(decode(bytes).unwrap(), ("é".to_owned(), *encoding));
+        }
+        assert_eq!(decode(b"").unwrap(), (String::new(), TEncoding::Utf8));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = decode(b"a: 1\nb: \xff").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid UTF-8 byte sequence at byte offset 8 at line 2 column 4"
+        );
+        assert_eq!(err.marker().index(), 8);
+
+        let mut bytes = utf16("\u{feff}ab", false);
+        bytes.extend_from_slice(&[0x00, 0xD8, b'c', 0]);
+        let err = decode(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid UTF-16LE byte sequence at byte offset 6"));
+        assert_eq!(err.marker().col(), 2);
+
+        let err = decode(&utf16("ab", true)[..3]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid UTF-16BE byte sequence at byte offset 2"));
+
+        let mut bytes = utf32("a", true);
+        bytes.extend_from_slice(&[0, 0x11, 0, 0]);
+        let err = decode(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid UTF-32BE byte sequence at byte offset 4"));
+        let err = decode(&utf32("ab", false)[..6]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid UTF-32LE byte sequence at byte offset 4"));
+    }
+}
@@ -0,0 +1,430 @@
+//! Layered configuration that remembers where every value came from.
+
+use crate::diff::write_flow;
+use crate::merge::{check, merge_node, MergeError, MergeHook, MergeOptions, TypeConflict};
+use crate::pointer::{array_index, find_key, untag, Pointer};
+use crate::scanner::{Marker, ScanError};
+use crate::yaml::{Hash, LoadableYamlNode, MarkedNode, MarkedYaml, Yaml, YamlLoader};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// Where a value of a `Layers` document was last set.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Origin {
+    /// The name the layer was added with, such as a file name, or
+    /// `env NAME` for an environment variable.
+    pub source: String,
+    /// Where the value starts in the source, if it was loaded from YAML.
+    pub mark: Option<Marker>,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mark {
+            Some(mark) => write!(f, "{}:{}:{}", self.source, mark.line(), mark.col() + 1),
+            None => f.write_str(&self.source),
+        }
+    }
+}
+
+/// An error from `Layers::add_str`.
+#[derive(Debug)]
+pub enum LayerError {
+    /// The source is not valid YAML.
+    Scan(ScanError),
+    /// The layer conflicts with the document, see `TypeConflict::Error`.
+    Merge(MergeError),
+}
+
+impl Error for LayerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LayerError::Scan(ref err) => Some(err),
+            LayerError::Merge(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LayerError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+            LayerError::Merge(ref err) => fmt::Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<ScanError> for LayerError {
+    fn from(err: ScanError) -> Self {
+        LayerError::Scan(err)
+    }
+}
+
+impl From<MergeError> for LayerError {
+    fn from(err: MergeError) -> Self {
+        LayerError::Merge(err)
+    }
+}
+
+/// A document merged from layers, such as a base file, an environment
+/// file and environment variables, with the `Origin` of every node.
+///
+/// Layers are merged like `merge_with_options`. A node from a layer gets
+/// the origin of that layer, and keeps it until a later layer replaces it;
+/// a mapping or a sequence that is merged into keeps its own origin.
+///
+/// ```
+/// use yaml_rust::layer::Layers;
+///
+/// let mut layers = Layers::new();
+/// layers.add_str("base.yaml", "db:\n  host: localhost\n  port: 5432\n").unwrap();
+/// layers.add_str("prod.yaml", "db:\n  host: db.prod\n").unwrap();
+/// layers.add_vars("APP_", vec![("APP_DB__PORT".to_owned(), "6432".to_owned())]).unwrap();
+///
+/// assert_eq!(layers.doc()["db"]["host"].as_str(), Some("db.prod"));
+/// assert_eq!(
+///     layers.report(),
+///     "/db/host: db.prod  # prod.yaml:2:9\n/db/port: 6432  # env APP_DB__PORT\n"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Layers {
+    doc: Yaml,
+    origins: BTreeMap<Pointer, Origin>,
+    options: MergeOptions,
+}
+
+impl Default for Layers {
+    fn default() -> Layers {
+        Layers::with_options(MergeOptions::default())
+    }
+}
+
+impl Layers {
+    /// An empty document, which is null, merged with the default options.
+    pub fn new() -> Layers {
+        Layers::default()
+    }
+
+    /// An empty document, merged with `options`.
+    pub fn with_options(options: MergeOptions) -> Layers {
+        Layers {
+            doc: Yaml::Null,
+            origins: BTreeMap::new(),
+            options,
+        }
+    }
+
+    /// The merged document.
+    pub fn doc(&self) -> &Yaml {
+        &self.doc
+    }
+
+    /// The merged document, without the origins.
+    pub fn into_yaml(self) -> Yaml {
+        self.doc
+    }
+
+    /// The origin of the node at `path`, which is looked up like
+    /// `Pointer::get` does.
+    pub fn origin(&self, path: &Pointer) -> Option<&Origin> {
+        let mut node = &self.doc;
+        let mut resolved = Pointer::root();
+        for segment in path.segments() {
+            match *untag(node) {
+                Yaml::Array(ref v) => {
+                    let i = array_index(segment)?;
+                    node = v.get(i)?;
+                    resolved.push(Yaml::Integer(i as i64));
+                }
+                Yaml::Hash(ref h) => {
+                    let key = find_key(h, segment)?;
+                    node = &h[&key];
+                    resolved.push(key);
+                }
+                _ => return None,
+            }
+        }
+        self.origins.get(&resolved)
+    }
+
+    /// The origins of all nodes, by path.
+    pub fn origins(&self) -> &BTreeMap<Pointer, Origin> {
+        &self.origins
+    }
+
+    /// Load `text` and merge its documents in order. If it fails, nothing
+    /// is merged.
+    pub fn add_str(&mut self, source: &str, text: &str) -> Result<(), LayerError> {
+        let docs = YamlLoader::load_marked_from_str(text)?;
+        let mut layers = self.clone();
+        for doc in docs {
+            layers.add(source, doc)?;
+        }
+        *self = layers;
+        Ok(())
+    }
+
+    /// Merge a document loaded with `YamlLoader::load_marked_from_str`.
+    pub fn add(&mut self, source: &str, doc: MarkedYaml) -> Result<(), MergeError> {
+        self.add_layer(source, doc, true)
+    }
+
+    /// Merge the variables that start with `prefix`, in order of name. The
+    /// rest of a name is a path of lowercase keys separated by `__`, so
+    /// `APP_DB__HOST` sets `/db/host` for the prefix `APP_`, and a value is
+    /// read like a plain scalar. If it fails, nothing is merged.
+    pub fn add_vars<I>(&mut self, prefix: &str, vars: I) -> Result<(), MergeError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars: Vec<_> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(prefix) && name.len() > prefix.len())
+            .collect();
+        vars.sort();
+        let mut layers = self.clone();
+        for (name, value) in vars {
+            let mut node = Yaml::from_str(&value);
+            for key in name[prefix.len()..].rsplit("__") {
+                let mut hash = Hash::new();
+                hash.insert(Yaml::String(key.to_lowercase()), node);
+                node = Yaml::Hash(hash);
+            }
+            let start = Marker::new(0, 1, 0);
+            let node = MarkedYaml::from_bare_yaml(node, start, start);
+            layers.add_layer(&format!("env {}", name), node, false)?;
+        }
+        *self = layers;
+        Ok(())
+    }
+
+    /// Merge the environment variables of the process that start with
+    /// `prefix`, see `add_vars`.
+    pub fn add_env(&mut self, prefix: &str) -> Result<(), MergeError> {
+        self.add_vars(prefix, std::env::vars())
+    }
+
+    fn add_layer(&mut self, source: &str, doc: MarkedYaml, marked: bool) -> Result<(), MergeError> {
+        if self.options.conflicts == TypeConflict::Error {
+            check(&mut Pointer::root(), &self.doc, &doc, &self.options)?;
+        }
+        let mut layer = Layer {
+            origins: &mut self.origins,
+            source,
+            marked,
+        };
+        merge_node(
+            &mut Pointer::root(),
+            &mut self.doc,
+            doc,
+            &self.options,
+            &mut layer,
+        );
+        Ok(())
+    }
+
+    /// The scalars and empty collections of the document, one per line in
+    /// document order, with their paths and origins.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        self.report_node(&mut Pointer::root(), &self.doc, &mut out)
+            .unwrap();
+        out
+    }
+
+    fn report_node(&self, path: &mut Pointer, node: &Yaml, out: &mut String) -> fmt::Result {
+        match *untag(node) {
+            Yaml::Array(ref v) if !v.is_empty() => {
+                for (i, x) in v.iter().enumerate() {
+                    path.push(Yaml::Integer(i as i64));
+                    self.report_node(path, x, out)?;
+                    path.pop();
+                }
+                return Ok(());
+            }
+            Yaml::Hash(ref h) if !h.is_empty() => {
+                for (k, v) in h {
+                    path.push(k.clone());
+                    self.report_node(path, v, out)?;
+                    path.pop();
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        write!(out, "{}: ", path)?;
+        write_flow(out, node)?;
+        if let Some(origin) = self.origins.get(path) {
+            write!(out, "  # {}", origin)?;
+        }
+        out.push('\n');
+        Ok(())
+    }
+}
+
+// Records the origins of the nodes that a layer sets.
+struct Layer<'a> {
+    origins: &'a mut BTreeMap<Pointer, Origin>,
+    source: &'a str,
+    marked: bool,
+}
+
+impl<'a> MergeHook<MarkedYaml> for Layer<'a> {
+    // Replaces the origins below `path` with those of `overlay`.
+    fn set(&mut self, path: &mut Pointer, overlay: &MarkedYaml) {
+        let stale: Vec<Pointer> = self
+            .origins
+            .range(path.clone()..)
+            .take_while(|(p, _)| p.segments().starts_with(path.segments()))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for p in stale {
+            self.origins.remove(&p);
+        }
+        self.record(path, overlay);
+    }
+}
+
+impl<'a> Layer<'a> {
+    fn record(&mut self, path: &mut Pointer, node: &MarkedYaml) {
+        let origin = Origin {
+            source: self.source.to_owned(),
+            mark: if self.marked { Some(node.start) } else { None },
+        };
+        self.origins.insert(path.clone(), origin);
+        match node.node {
+            MarkedNode::Array(ref v) => {
+                for (i, x) in v.iter().enumerate() {
+                    path.push(Yaml::Integer(i as i64));
+                    self.record(path, x);
+                    path.pop();
+                }
+            }
+            MarkedNode::Hash(ref h) => {
+                for (k, v) in h {
+                    path.push(k.clone().into_yaml());
+                    self.record(path, v);
+                    path.pop();
+                }
+            }
+            MarkedNode::Tagged(_, ref v) => self.record(path, v),
+            MarkedNode::Scalar(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merge::ArrayMerge;
+
+    fn origin(layers: &Layers, path: &str) -> String {
+        layers
+            .origin(&Pointer::parse(path).unwrap())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_origins() {
+        let mut layers = Layers::new();
+        layers
+            .add_str(
+                "base.yaml",
+                "db: {host: a, port: 1}\nports: [80]\nname: x\n",
+            )
+            .unwrap();
+        layers
+            .add_str(
+                "prod.yaml",
+                "db:\n  host: b\nports: [443, 8443]\nextra: {k: v}\n",
+            )
+            .unwrap();
+        assert_eq!(origin(&layers, ""), "base.yaml:1:1");
+        assert_eq!(origin(&layers, "/db"), "base.yaml:1:5");
+        assert_eq!(origin(&layers, "/db/host"), "prod.yaml:2:9");
+        assert_eq!(origin(&layers, "/db/port"), "base.yaml:1:21");
+        assert_eq!(origin(&layers, "/ports"), "prod.yaml:3:8");
+        assert_eq!(origin(&layers, "/ports/1"), "prod.yaml:3:14");
+        assert_eq!(origin(&layers, "/extra/k"), "prod.yaml:4:12");
+        assert_eq!(layers.origins().len(), 10);
+
+        // replacing a mapping with a scalar drops the origins below it
+        layers
+            .add_vars("APP_", vec![("APP_DB".to_owned(), "none".to_owned())])
+            .unwrap();
+        assert_eq!(origin(&layers, "/db"), "env APP_DB");
+        assert_eq!(layers.origin(&Pointer::parse("/db/host").unwrap()), None);
+        assert_eq!(
+            layers.report(),
+            "/db: none  # env APP_DB
+/ports/0: 443  # prod.yaml:3:9
+/ports/1: 8443  # prod.yaml:3:14
+/name: x  # base.yaml:3:7
+/extra/k: v  # prod.yaml:4:12
+"
+        );
+    }
+
+    #[test]
+    fn test_array_origins() {
+        let mut layers = Layers::with_options(MergeOptions {
+            arrays: ArrayMerge::ByKey("name".to_owned()),
+            ..Default::default()
+        });
+        layers
+            .add_str("a", "- {name: x, v: 1}\n- {name: y, v: 2}\n")
+            .unwrap();
+        layers
+            .add_str("b", "- {name: y, v: 3}\n- {name: z}\n- []\n")
+            .unwrap();
+        assert_eq!(
+            layers.report(),
+            "/0/name: x  # a:1:10
+/0/v: 1  # a:1:16
+/1/name: y  # b:1:10
+/1/v: 3  # b:1:16
+/2/name: z  # b:2:10
+/3: []  # b:3:3
+"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut layers = Layers::with_options(MergeOptions {
+            conflicts: TypeConflict::Error,
+            ..Default::default()
+        });
+        layers.add_str("a", "db: {host: x}").unwrap();
+        let before = layers.report();
+
+        let err = layers.add_str("b", "name: y\n---\ndb: [1]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot merge a sequence into a mapping at `/db`"
+        );
+        let err = layers.add_str("b", "db: {host: [}").unwrap_err();
+        assert!(matches!(err, LayerError::Scan(_)));
+        let vars = vec![
+            ("APP_NAME".to_owned(), "y".to_owned()),
+            ("APP_DB__HOST__X".to_owned(), "1".to_owned()),
+        ];
+        let err = layers.add_vars("APP_", vars).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot merge a mapping into a scalar at `/db/host`"
+        );
+        assert_eq!(layers.report(), before);
+
+        // a tagged mapping is merged, not replaced
+        layers.add_str("c", "tls: !cfg {cert: a, key: b}").unwrap();
+        layers.add_str("d", "tls: {cert: c}").unwrap();
+        assert_eq!(origin(&layers, "/tls/cert"), "d:1:13");
+        assert_eq!(origin(&layers, "/tls/key"), "c:1:26");
+        let expected = YamlLoader::load_from_str("db: {host: x}\ntls: !cfg {cert: c, key: b}");
+        assert_eq!(*layers.doc(), expected.unwrap()[0]);
+    }
+}
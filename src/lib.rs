@@ -10,7 +10,7 @@
 //!
 //! ```toml
 //! [dependencies]
-//! yaml-rust = "0.4"
+//! yaml-rust = "0.5"
 //! ```
 //!
 //! And this in your crate root:
@@ -36,7 +36,7 @@
 //!
 //! ```
 
-#![doc(html_root_url = "https://docs.rs/yaml-rust/0.4.5")]
+#![doc(html_root_url = "https://docs.rs/yaml-rust/0.5.0")]
 #![cfg_attr(feature = "cargo-clippy", allow(renamed_and_removed_lints))]
 #![cfg_attr(feature = "cargo-clippy", warn(cyclomatic_complexity))]
 #![cfg_attr(
@@ -46,16 +46,35 @@
 
 extern crate linked_hash_map;
 
+mod base64;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
 pub mod emitter;
+mod encoding;
+pub mod layer;
+pub mod merge;
+pub mod number;
 pub mod parser;
+pub mod patch;
+pub mod pointer;
+pub mod query;
 pub mod scanner;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod timestamp;
 pub mod yaml;
 
 // reexport key APIs
-pub use crate::emitter::{EmitError, YamlEmitter};
+pub use crate::emitter::{EmitError, JsonEmitter, JsonFallback, YamlEmitter};
 pub use crate::parser::Event;
 pub use crate::scanner::ScanError;
-pub use crate::yaml::{Yaml, YamlLoader};
+pub use crate::schema::Schema;
+pub use crate::yaml::{
+    Documents, DuplicateKeys, LoadError, LoaderOptions, MarkedYaml, SharedYaml, TagConstructors,
+    Yaml, YamlLoader,
+};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,518 @@
+//! Deep merging of `Yaml` documents, for layered configuration.
+
+use crate::pointer::{untag, untag_mut, Pointer};
+use crate::scanner::Marker;
+use crate::yaml::{LoadableYamlNode, MarkedNode, MarkedYaml, Yaml};
+use linked_hash_map::LinkedHashMap;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+/// How `merge_with_options` combines two sequences.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ArrayMerge {
+    /// The overriding sequence replaces the base one.
+    Replace,
+    /// The elements of the overriding sequence are appended.
+    Append,
+    /// Mappings with the same value for this key, such as `name`, are
+    /// merged, and other elements are appended.
+    ByKey(String),
+}
+
+impl Default for ArrayMerge {
+    fn default() -> ArrayMerge {
+        ArrayMerge::Replace
+    }
+}
+
+/// What `merge_with_options` does when a sequence or a mapping meets a node
+/// of another kind. A null in the base document is never a conflict.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum TypeConflict {
+    /// Fail with a `MergeError`.
+    Error,
+    /// The overriding node replaces the base one.
+    Override,
+}
+
+impl Default for TypeConflict {
+    fn default() -> TypeConflict {
+        TypeConflict::Override
+    }
+}
+
+/// Options for `merge_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    /// How sequences are combined. Defaults to `ArrayMerge::Replace`.
+    pub arrays: ArrayMerge,
+    /// What a type conflict does. Defaults to `TypeConflict::Override`.
+    pub conflicts: TypeConflict,
+}
+
+/// The error for a type conflict, see `TypeConflict::Error`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MergeError {
+    info: String,
+    path: Pointer,
+}
+
+impl MergeError {
+    /// The path of the conflicting node in the base document.
+    pub fn path(&self) -> &Pointer {
+        &self.path
+    }
+
+    /// The error without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+}
+
+impl Error for MergeError {}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at `{}`", self.info, self.path)
+    }
+}
+
+/// Merge `overlay` into `base`, with the default options: sequences and
+/// nodes of another kind are replaced.
+///
+/// ```
+/// use yaml_rust::merge::merge;
+/// use yaml_rust::YamlLoader;
+///
+/// let load = |s| YamlLoader::load_from_str(s).unwrap().remove(0);
+/// let mut config = load("{db: {host: localhost, port: 5432}, debug: true}");
+/// merge(&mut config, load("{db: {host: db.prod}, workers: 4}")).unwrap();
+/// assert_eq!(
+///     config,
+///     load("{db: {host: db.prod, port: 5432}, debug: true, workers: 4}")
+/// );
+/// ```
+pub fn merge(base: &mut Yaml, overlay: Yaml) -> Result<(), MergeError> {
+    merge_with_options(base, overlay, &MergeOptions::default())
+}
+
+/// Merge `overlay` into `base`. Mappings are merged entry by entry, with the
+/// entries of `base` in their order followed by the new ones, and other
+/// nodes from `overlay` replace those in `base`. Tagged collections are
+/// merged like untagged ones, and keep the tag of `base` unless `overlay`
+/// has its own. If the merge fails, `base` is left unchanged.
+pub fn merge_with_options(
+    base: &mut Yaml,
+    overlay: Yaml,
+    options: &MergeOptions,
+) -> Result<(), MergeError> {
+    if options.conflicts == TypeConflict::Error {
+        check(&mut Pointer::root(), base, &overlay, options)?;
+    }
+    merge_node(&mut Pointer::root(), base, overlay, options, &mut ());
+    Ok(())
+}
+
+// A node that can be merged: a `Yaml`, or a `MarkedYaml` for `Layers`.
+pub(crate) trait MergeNode: LoadableYamlNode {
+    // The node inside its tags.
+    fn untagged(&self) -> &Self;
+
+    // The outer tag and the node inside it, or the node if it has no tag.
+    fn split_tag(self) -> Result<(String, Self), Self>;
+
+    // The node as a `Yaml`, copied only if it isn't one.
+    fn to_yaml(&self) -> Cow<'_, Yaml>;
+}
+
+impl MergeNode for Yaml {
+    fn untagged(&self) -> &Yaml {
+        untag(self)
+    }
+
+    fn split_tag(self) -> Result<(String, Yaml), Yaml> {
+        match self {
+            Yaml::Tagged(tag, inner) => Ok((tag, *inner)),
+            node => Err(node),
+        }
+    }
+
+    fn to_yaml(&self) -> Cow<'_, Yaml> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl MergeNode for MarkedYaml {
+    fn untagged(&self) -> &MarkedYaml {
+        match self.node {
+            MarkedNode::Tagged(_, ref inner) => inner.untagged(),
+            _ => self,
+        }
+    }
+
+    fn split_tag(self) -> Result<(String, MarkedYaml), MarkedYaml> {
+        match self.node {
+            MarkedNode::Tagged(tag, inner) => Ok((tag, *inner)),
+            node => Err(MarkedYaml { node, ..self }),
+        }
+    }
+
+    fn to_yaml(&self) -> Cow<'_, Yaml> {
+        Cow::Owned(self.clone().into_yaml())
+    }
+}
+
+// Called by `merge_node` for every node of the overlay that it sets.
+pub(crate) trait MergeHook<N> {
+    // `overlay` is about to replace the node at `path`, or to be added there.
+    fn set(&mut self, path: &mut Pointer, overlay: &N);
+}
+
+impl MergeHook<Yaml> for () {
+    fn set(&mut self, _: &mut Pointer, _: &Yaml) {}
+}
+
+// Finds a type conflict before anything is changed.
+pub(crate) fn check<B: MergeNode, O: MergeNode>(
+    path: &mut Pointer,
+    base: &B,
+    overlay: &O,
+    options: &MergeOptions,
+) -> Result<(), MergeError> {
+    let (base, overlay) = (base.untagged(), overlay.untagged());
+    if let (Some(base), Some(overlay)) = (base.hash_ref(), overlay.hash_ref()) {
+        for (k, v) in overlay {
+            let k: B = as_key(k.to_yaml().into_owned());
+            if let Some(b) = base.get(&k) {
+                path.push(k.into_bare_yaml());
+                check(path, b, v, options)?;
+                path.pop();
+            }
+        }
+    } else if let (Some(base), Some(overlay)) = (base.array_ref(), overlay.array_ref()) {
+        if let ArrayMerge::ByKey(ref key) = options.arrays {
+            // the index each element of `overlay` is merged into; the
+            // elements with the same key are also merged into each other
+            let mut targets = Vec::with_capacity(overlay.len());
+            let mut len = base.len();
+            for (n, v) in overlay.iter().enumerate() {
+                let target = match find_keyed(base, v, key) {
+                    Some(i) => i,
+                    None => match find_keyed(&overlay[..n], v, key) {
+                        Some(m) => targets[m],
+                        None => {
+                            len += 1;
+                            len - 1
+                        }
+                    },
+                };
+                path.push(Yaml::Integer(target as i64));
+                if target < base.len() {
+                    check(path, &base[target], v, options)?;
+                }
+                for (w, _) in overlay.iter().zip(&targets).filter(|&(_, &t)| t == target) {
+                    check(path, w, v, options)?;
+                }
+                path.pop();
+                targets.push(target);
+            }
+        }
+    } else if !base.is_null() && kind(base) != kind(overlay) {
+        return Err(MergeError {
+            info: format!("cannot merge a {} into a {}", kind(overlay), kind(base)),
+            path: path.clone(),
+        });
+    }
+    Ok(())
+}
+
+// Merges `overlay` into `base`, which is at `path` in the document.
+pub(crate) fn merge_node<N: MergeNode, H: MergeHook<N>>(
+    path: &mut Pointer,
+    base: &mut Yaml,
+    overlay: N,
+    options: &MergeOptions,
+    hook: &mut H,
+) {
+    if !merges_into(base, &overlay, options) {
+        hook.set(path, &overlay);
+        *base = overlay.into_bare_yaml();
+        return;
+    }
+    let mut overlay = match overlay.split_tag() {
+        Ok((tag, overlay)) => {
+            merge_node(path, base, overlay, options, hook);
+            retag(base, tag);
+            return;
+        }
+        Err(overlay) => overlay,
+    };
+    match *untag_mut(base) {
+        Yaml::Hash(ref mut base) => {
+            let overlay = mem::replace(overlay.hash_mut().unwrap(), LinkedHashMap::new());
+            for (k, v) in overlay {
+                let k = k.into_bare_yaml();
+                path.push(k.clone());
+                match base.get_mut(&k) {
+                    Some(b) => merge_node(path, b, v, options, hook),
+                    None => {
+                        hook.set(path, &v);
+                        base.insert(k, v.into_bare_yaml());
+                    }
+                }
+                path.pop();
+            }
+        }
+        Yaml::Array(ref mut base) => {
+            let overlay = mem::replace(overlay.array_mut().unwrap(), Vec::new());
+            for v in overlay {
+                let found = match options.arrays {
+                    ArrayMerge::ByKey(ref key) => find_keyed(base, &v, key),
+                    _ => None,
+                };
+                let i = found.unwrap_or(base.len());
+                path.push(Yaml::Integer(i as i64));
+                match found {
+                    Some(i) => merge_node(path, &mut base[i], v, options, hook),
+                    None => {
+                        hook.set(path, &v);
+                        base.push(v.into_bare_yaml());
+                    }
+                }
+                path.pop();
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+// Whether `overlay` is merged into `base`, looking through their tags,
+// rather than replacing it.
+fn merges_into<N: MergeNode>(base: &Yaml, overlay: &N, options: &MergeOptions) -> bool {
+    match (untag(base), kind(overlay)) {
+        (Yaml::Hash(_), "mapping") => true,
+        (Yaml::Array(_), "sequence") => options.arrays != ArrayMerge::Replace,
+        _ => false,
+    }
+}
+
+// Gives `node` the tag of a merged overlay in place of its own.
+fn retag(node: &mut Yaml, tag: String) {
+    match *node {
+        Yaml::Tagged(ref mut t, _) => *t = tag,
+        _ => {
+            let inner = mem::replace(node, Yaml::Null);
+            *node = Yaml::Tagged(tag, Box::new(inner));
+        }
+    }
+}
+
+// The kind of a node, looking through its tags, like `yaml::kind`.
+fn kind<N: MergeNode>(node: &N) -> &'static str {
+    let node = node.untagged();
+    if node.hash_ref().is_some() {
+        "mapping"
+    } else if node.array_ref().is_some() {
+        "sequence"
+    } else {
+        "scalar"
+    }
+}
+
+// A mapping key with the value of `key`.
+fn as_key<N: MergeNode>(key: Yaml) -> N {
+    let mark = Marker::new(0, 1, 0);
+    N::from_bare_yaml(key, mark, mark)
+}
+
+// The index of the mapping in `base` with the same value for `key` as `v`.
+fn find_keyed<B: MergeNode, O: MergeNode>(base: &[B], v: &O, key: &str) -> Option<usize> {
+    let key = Yaml::String(key.to_owned());
+    let value = v
+        .untagged()
+        .hash_ref()?
+        .get(&as_key(key.clone()))?
+        .to_yaml();
+    let key: B = as_key(key);
+    base.iter().position(|b| {
+        b.untagged()
+            .hash_ref()
+            .and_then(|b| b.get(&key))
+            .map_or(false, |w| w.to_yaml() == value)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    fn merged(base: &str, overlay: &str, options: &MergeOptions) -> Result<Yaml, MergeError> {
+        let mut doc = load(base);
+        merge_with_options(&mut doc, load(overlay), options)?;
+        Ok(doc)
+    }
+
+    #[test]
+    fn test_merge_hashes() {
+        let options = MergeOptions::default();
+        let doc = merged(
+            "{a: 1, b: {x: 1, y: 2}, c: [1, 2], d: ~}",
+            "{e: 5, b: {z: 3, x: 0}, c: [3], d: {k: v}, a: ~}",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            doc,
+            load("{a: ~, b: {x: 0, y: 2, z: 3}, c: [3], d: {k: v}, e: 5}")
+        );
+        let keys: Vec<_> = doc
+            .as_hash()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["a", "b", "c", "d", "e"]);
+        let keys: Vec<_> = doc["b"]
+            .as_hash()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["x", "y", "z"]);
+
+        assert_eq!(merged("{a: 1}", "[2]", &options).unwrap(), load("[2]"));
+        assert_eq!(
+            merged("{a: [1]}", "{a: x}", &options).unwrap(),
+            load("{a: x}")
+        );
+    }
+
+    #[test]
+    fn test_merge_arrays() {
+        let base = "{a: [{name: x, v: 1}, {name: y, v: 2}, 3]}";
+        let overlay = "{a: [{name: y, v: 0, w: 1}, {name: z}, 4, {v: 5}]}";
+        let cases = [
+            (ArrayMerge::Replace, overlay),
+            (
+                ArrayMerge::Append,
+                "{a: [{name: x, v: 1}, {name: y, v: 2}, 3, {name: y, v: 0, w: 1}, {name: z}, 4, {v: 5}]}",
+            ),
+            (
+                ArrayMerge::ByKey("name".to_owned()),
+                "{a: [{name: x, v: 1}, {name: y, v: 0, w: 1}, 3, {name: z}, 4, {v: 5}]}",
+            ),
+        ];
+        for (arrays, expected) in cases.iter().cloned() {
+            let options = MergeOptions {
+                arrays,
+                ..Default::default()
+            };
+            assert_eq!(merged(base, overlay, &options).unwrap(), load(expected));
+        }
+    }
+
+    #[test]
+    fn test_type_conflicts() {
+        let options = MergeOptions {
+            arrays: ArrayMerge::ByKey("name".to_owned()),
+            conflicts: TypeConflict::Error,
+        };
+        // scalars of different types, and nulls, are not conflicts
+        assert_eq!(
+            merged("{a: 1, b: ~}", "{a: x, b: [1]}", &options).unwrap(),
+            load("{a: x, b: [1]}")
+        );
+
+        let cases = [
+            (
+                "{a: {b: 1}}",
+                "{a: [1]}",
+                "cannot merge a sequence into a mapping at `/a`",
+            ),
+            (
+                "{a: {b: 1}}",
+                "{a: {b: {c: 1}}}",
+                "cannot merge a mapping into a scalar at `/a/b`",
+            ),
+            (
+                "[{name: x, v: [1]}]",
+                "[{name: x, v: 2}]",
+                "cannot merge a scalar into a sequence at `/0/v`",
+            ),
+            (
+                "{a: {b: 1}}",
+                "{a: ~}",
+                "cannot merge a scalar into a mapping at `/a`",
+            ),
+            (
+                "{a: !cfg {b: {c: 1}}}",
+                "{a: {b: [1]}}",
+                "cannot merge a sequence into a mapping at `/a/b`",
+            ),
+            // overlay elements with the same key are merged into each other
+            (
+                "[{name: x}]",
+                "[{name: y, v: [1]}, {name: y, v: 2}]",
+                "cannot merge a scalar into a sequence at `/1/v`",
+            ),
+            (
+                "[{name: x}]",
+                "[{name: x, v: {k: 1}}, {name: x, v: [2]}]",
+                "cannot merge a sequence into a mapping at `/0/v`",
+            ),
+        ];
+        for &(base, overlay, expected) in &cases {
+            let mut doc = load(base);
+            let err = merge_with_options(&mut doc, load(overlay), &options).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+            assert_eq!(doc, load(base));
+        }
+
+        assert_eq!(
+            merged(
+                "[{name: x}]",
+                "[{name: y, a: 1}, {v: 1}, {name: y, b: 2}]",
+                &options
+            )
+            .unwrap(),
+            load("[{name: x}, {name: y, a: 1, b: 2}, {v: 1}]")
+        );
+    }
+
+    #[test]
+    fn test_merge_tagged() {
+        let options = MergeOptions {
+            arrays: ArrayMerge::ByKey("name".to_owned()),
+            conflicts: TypeConflict::Error,
+        };
+        let cases = [
+            // the tag of the base is kept, unless the overlay has its own
+            (
+                "{a: !cfg {x: 1, y: 2}}",
+                "{a: {x: 9}}",
+                "{a: !cfg {x: 9, y: 2}}",
+            ),
+            ("{a: !cfg {x: 1}}", "{a: !t {y: 2}}", "{a: !t {x: 1, y: 2}}"),
+            ("{a: {x: 1}}", "{a: !t {y: 2}}", "{a: !t {x: 1, y: 2}}"),
+            (
+                "[!s {name: x, v: 1}, !s {name: y}]",
+                "[{name: x, w: 2}, !t {name: y}]",
+                "[!s {name: x, v: 1, w: 2}, !t {name: y}]",
+            ),
+        ];
+        for &(base, overlay, expected) in &cases {
+            assert_eq!(merged(base, overlay, &options).unwrap(), load(expected));
+        }
+        assert_eq!(
+            merged("{a: !cfg {x: 1}}", "{a: 1}", &MergeOptions::default()).unwrap(),
+            load("{a: 1}")
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! A number type that keeps the literal it was written as.
+
+use crate::yaml::{parse_f64, Yaml};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash;
+
+#[derive(Clone, Copy, Debug)]
+enum Value {
+    Integer(i128),
+    Float(f64),
+}
+
+/// An integer or a float, together with the radix and text it was written
+/// in.
+///
+/// `YamlLoader` loads numbers as `Yaml::Number` when
+/// `LoaderOptions::numbers` is set. The value is parsed once, numbers
+/// compare and order by value rather than by text, and `YamlEmitter` writes
+/// the original literal, so a file mode such as `0o755` stays readable.
+///
+/// Numbers with the same value are equal whatever their spelling, but an
+/// integer is never equal to a float, and orders before a float of the same
+/// value. Integers beyond 2^53 are compared to floats by their nearest
+/// `f64`. A NaN is equal to any other NaN and greater than every other
+/// number.
+///
+/// ```
+/// use yaml_rust::number::Number;
+///
+/// let mode = Number::with_radix(0o755, 8).unwrap();
+/// assert_eq!(mode.to_string(), "0o755");
+/// assert_eq!(mode.as_i64(), Some(493));
+/// assert_eq!(mode, Number::from(493));
+/// assert!(Number::from(1.5) > Number::from(1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Number {
+    value: Value,
+    radix: u32,
+    literal: String,
+}
+
+impl Number {
+    /// An integer written in radix 8, 10 or 16, with a `0o` or `0x` prefix
+    /// for the former. Returns `None` for other radixes, and for negative
+    /// integers in radix 8 or 16, which YAML has no syntax for.
+    pub fn with_radix(value: i128, radix: u32) -> Option<Number> {
+        let literal = match radix {
+            10 => value.to_string(),
+            8 if value >= 0 => format!("0o{:o}", value),
+            16 if value >= 0 => format!("0x{:x}", value),
+            _ => return None,
+        };
+        Some(Number {
+            value: Value::Integer(value),
+            radix,
+            literal,
+        })
+    }
+
+    // The number a plain scalar was resolved to, keeping the text it was
+    // written as.
+    pub(crate) fn from_resolved(literal: String, resolved: &Yaml) -> Option<Number> {
+        let value = match *resolved {
+            Yaml::Integer(i) => Value::Integer(i128::from(i)),
+            Yaml::BigInteger(i) => Value::Integer(i),
+            // YAML 1.1 reals are normalized, so parse the resolved text
+            Yaml::Real(ref v) => Value::Float(parse_f64(v)?),
+            _ => return None,
+        };
+        let digits = literal.trim_start_matches(&['-', '+'][..]);
+        let radix = match value {
+            Value::Float(_) => 10,
+            _ if digits.starts_with("0x") => 16,
+            _ if digits.starts_with("0o") => 8,
+            _ if digits.starts_with("0b") => 2,
+            _ if digits.contains(':') => 60,
+            // a leading zero means octal in YAML 1.1 only
+            Value::Integer(i) if digits.starts_with('0') => {
+                let abs = if i < 0 {
+                    (i as u128).wrapping_neg()
+                } else {
+                    i as u128
+                };
+                match digits.replace('_', "").parse::<u128>() {
+                    Ok(decimal) if decimal == abs => 10,
+                    _ => 8,
+                }
+            }
+            Value::Integer(_) => 10,
+        };
+        Some(Number {
+            value,
+            radix,
+            literal,
+        })
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self.value {
+            Value::Integer(_) => true,
+            Value::Float(_) => false,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        !self.is_integer()
+    }
+
+    /// The value of an integer.
+    pub fn as_i128(&self) -> Option<i128> {
+        match self.value {
+            Value::Integer(i) => Some(i),
+            Value::Float(_) => None,
+        }
+    }
+
+    /// The value of an integer, if it fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_i128().and_then(|i| i64::try_from(i).ok())
+    }
+
+    /// The value of a float, or the nearest `f64` to an integer.
+    pub fn as_f64(&self) -> f64 {
+        match self.value {
+            Value::Integer(i) => i as f64,
+            Value::Float(f) => f,
+        }
+    }
+
+    /// The radix the number was written in: 2, 8, 10 or 16, or 60 for
+    /// YAML 1.1 sexagesimal numbers such as `1:30`. Floats are always 10.
+    pub fn radix(&self) -> u32 {
+        self.radix
+    }
+
+    /// The text the number was written as.
+    pub fn literal(&self) -> &str {
+        &self.literal
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Number {
+        Number::with_radix(i128::from(value), 10).unwrap()
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Number {
+        let literal = if value.is_nan() {
+            ".nan".to_owned()
+        } else if value.is_infinite() {
+            if value > 0.0 { ".inf" } else { "-.inf" }.to_owned()
+        } else {
+            format!("{:?}", value)
+        };
+        Number {
+            value: Value::Float(value),
+            radix: 10,
+            literal,
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    /// Writes the literal.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.literal)
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Number) -> Ordering {
+        match (self.value, other.value) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(&b),
+            (Value::Float(a), Value::Float(b)) => cmp_f64(a, b),
+            (Value::Integer(a), Value::Float(b)) => cmp_f64(a as f64, b).then(Ordering::Less),
+            (Value::Float(a), Value::Integer(b)) => cmp_f64(a, b as f64).then(Ordering::Greater),
+        }
+    }
+}
+
+// Orders NaN after every other float, so that the order is total.
+fn cmp_f64(a: f64, b: f64) -> Ordering {
+    match a.partial_cmp(&b) {
+        Some(ordering) => ordering,
+        None => a.is_nan().cmp(&b.is_nan()),
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl hash::Hash for Number {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        match self.value {
+            Value::Integer(i) => (0u8, i).hash(state),
+            // equal floats with different bits: every NaN, and -0.0 and 0.0
+            Value::Float(f) if f.is_nan() => 2u8.hash(state),
+            Value::Float(f) => (1u8, if f == 0.0 { 0 } else { f.to_bits() }).hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn resolved(v: &str) -> Number {
+        Number::from_resolved(v.to_owned(), &Yaml::from_str(v)).unwrap()
+    }
+
+    #[test]
+    fn test_from_resolved() {
+        let n = resolved("0x1F");
+        assert_eq!((n.as_i64(), n.radix(), n.literal()), (Some(31), 16, "0x1F"));
+        let n = resolved("0o755");
+        assert_eq!((n.as_i64(), n.radix()), (Some(0o755), 8));
+        let n = resolved("-012");
+        assert_eq!((n.as_i64(), n.radix()), (Some(-12), 10));
+        let n = resolved("1.50");
+        assert_eq!((n.as_f64(), n.literal()), (1.5, "1.50"));
+        assert!(n.is_float());
+        assert_eq!(resolved(".inf").as_f64(), f64::INFINITY);
+        assert!(Number::from_resolved("a".to_owned(), &Yaml::from_str("a")).is_none());
+
+        let octal = Number::from_resolved("014".to_owned(), &Yaml::Integer(12)).unwrap();
+        assert_eq!(octal.radix(), 8);
+        let sexagesimal = Number::from_resolved("1:30".to_owned(), &Yaml::Integer(90)).unwrap();
+        assert_eq!(sexagesimal.radix(), 60);
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(Number::with_radix(31, 16).unwrap().literal(), "0x1f");
+        assert_eq!(Number::with_radix(-31, 10).unwrap().literal(), "-31");
+        assert!(Number::with_radix(-31, 16).is_none());
+        assert!(Number::with_radix(5, 2).is_none());
+        assert_eq!(Number::from(1.0).literal(), "1.0");
+        assert_eq!(Number::from(-f64::INFINITY).literal(), "-.inf");
+        assert_eq!(Number::from(f64::NAN).literal(), ".nan");
+        assert_eq!(Number::with_radix(i128::MAX, 10).unwrap().as_i64(), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut numbers = [
+            resolved("0x10"),
+            resolved("2.5"),
+            Number::from(f64::NAN),
+            Number::from(2.0),
+            Number::from(-1),
+            Number::from(2),
+            resolved("-.inf"),
+        ];
+        numbers.sort();
+        let literals: Vec<_> = numbers.iter().map(Number::literal).collect();
+        assert_eq!(literals, ["-.inf", "-1", "2", "2.0", "2.5", "0x10", ".nan"]);
+
+        assert_eq!(resolved("0x1F"), resolved("31"));
+        assert_ne!(Number::from(2), Number::from(2.0));
+        assert_eq!(Number::from(f64::NAN), Number::from(f64::NAN));
+        assert_eq!(Number::from(-0.0), Number::from(0.0));
+        assert!(Number::from(f64::NAN) > Number::from(f64::INFINITY));
+        let set: HashSet<_> = [
+            Number::from(-0.0),
+            Number::from(0.0),
+            Number::from(-f64::NAN),
+        ]
+        .iter()
+        .cloned()
+        .chain(Some(Number::from(f64::NAN)))
+        .collect();
+        assert_eq!(set.len(), 2);
+    }
+}
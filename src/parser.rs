@@ -35,7 +35,8 @@ enum State {
 pub enum Event {
     /// Reserved for internal use
     Nothing,
-    StreamStart,
+    /// The encoding of the stream
+    StreamStart(TEncoding),
     StreamEnd,
     DocumentStart,
     DocumentEnd,
@@ -43,11 +44,11 @@ pub enum Event {
     Alias(usize),
     /// Value, style, anchor_id, tag
     Scalar(String, TScalarStyle, usize, Option<TokenType>),
-    /// Anchor ID
-    SequenceStart(usize),
+    /// Anchor ID, tag
+    SequenceStart(usize, Option<TokenType>),
     SequenceEnd,
-    /// Anchor ID
-    MappingStart(usize),
+    /// Anchor ID, tag
+    MappingStart(usize, Option<TokenType>),
     MappingEnd,
 }
 
@@ -62,6 +63,34 @@ impl Event {
     }
 }
 
+/// Limits on the input accepted by a `Parser`, for reading untrusted YAML.
+///
+/// All limits default to `None`, meaning unlimited. Exceeding one fails
+/// parsing with a `ScanError` whose `limit()` names it.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+pub struct ParserOptions {
+    /// Maximum nesting depth of sequences and mappings.
+    pub max_depth: Option<usize>,
+    /// Maximum length of a scalar, in bytes.
+    pub max_scalar_length: Option<usize>,
+    /// Maximum number of nodes in the stream. Each alias counts as one node.
+    pub max_nodes: Option<usize>,
+    /// Maximum number of documents in the stream.
+    pub max_documents: Option<usize>,
+}
+
+fn check_limit(
+    max: Option<usize>,
+    count: usize,
+    limit: Limit,
+    mark: Marker,
+) -> Result<(), ScanError> {
+    match max {
+        Some(max) if count > max => Err(ScanError::limit_exceeded(mark, limit)),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser<T> {
     scanner: Scanner<T>,
@@ -72,6 +101,12 @@ pub struct Parser<T> {
     current: Option<(Event, Marker)>,
     anchors: HashMap<String, usize>,
     anchor_id: usize,
+    scalar_end: Option<Marker>,
+    span_end: Marker,
+    options: ParserOptions,
+    depth: usize,
+    nodes: usize,
+    documents: usize,
 }
 
 pub trait EventReceiver {
@@ -80,6 +115,13 @@ pub trait EventReceiver {
 
 pub trait MarkedEventReceiver {
     fn on_event(&mut self, ev: Event, _mark: Marker);
+
+    /// Like `on_event`, but also receives the marker where the source of the
+    /// event ends. Scalars span their whole text, other events end where
+    /// they start.
+    fn on_spanned_event(&mut self, ev: Event, mark: Marker, _end: Marker) {
+        self.on_event(ev, mark)
+    }
 }
 
 impl<R: EventReceiver> MarkedEventReceiver for R {
@@ -92,8 +134,16 @@ pub type ParseResult = Result<(Event, Marker), ScanError>;
 
 impl<T: Iterator<Item = char>> Parser<T> {
     pub fn new(src: T) -> Parser<T> {
+        Parser::with_options(src, ParserOptions::default())
+    }
+
+    /// Create a parser that enforces the limits in `options`.
+    pub fn with_options(src: T, options: ParserOptions) -> Parser<T> {
+        let mut scanner = Scanner::new(src);
+        scanner.set_max_scalar_length(options.max_scalar_length);
+        let span_end = scanner.mark();
         Parser {
-            scanner: Scanner::new(src),
+            scanner,
             states: Vec::new(),
             state: State::StreamStart,
             marks: Vec::new(),
@@ -103,9 +153,27 @@ impl<T: Iterator<Item = char>> Parser<T> {
             anchors: HashMap::new(),
             // valid anchor_id starts from 1
             anchor_id: 1,
+            scalar_end: None,
+            span_end,
+            options,
+            depth: 0,
+            nodes: 0,
+            documents: 0,
         }
     }
 
+    /// The encoding reported by `Event::StreamStart`, for input that was
+    /// decoded from bytes before it reached the parser.
+    pub fn set_encoding(&mut self, encoding: TEncoding) {
+        self.scanner.set_encoding(encoding);
+    }
+
+    /// The marker where the source of the event last produced by the parser
+    /// ends. See `MarkedEventReceiver::on_spanned_event`.
+    pub fn span_end(&self) -> Marker {
+        self.span_end
+    }
+
     pub fn peek(&mut self) -> Result<&(Event, Marker), ScanError> {
         match self.current {
             Some(ref x) => Ok(x),
@@ -163,13 +231,45 @@ impl<T: Iterator<Item = char>> Parser<T> {
 
     fn parse(&mut self) -> ParseResult {
         if self.state == State::End {
-            return Ok((Event::StreamEnd, self.scanner.mark()));
+            self.span_end = self.scanner.mark();
+            return Ok((Event::StreamEnd, self.span_end));
         }
         let (ev, mark) = self.state_machine()?;
         // println!("EV {:?}", ev);
+        self.span_end = self.scalar_end.take().unwrap_or(mark);
+        self.check_limits(&ev, mark)?;
         Ok((ev, mark))
     }
 
+    fn check_limits(&mut self, ev: &Event, mark: Marker) -> Result<(), ScanError> {
+        match *ev {
+            Event::DocumentStart => {
+                self.documents += 1;
+                check_limit(
+                    self.options.max_documents,
+                    self.documents,
+                    Limit::Documents,
+                    mark,
+                )
+            }
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                self.depth += 1;
+                self.nodes += 1;
+                check_limit(self.options.max_depth, self.depth, Limit::Depth, mark)?;
+                check_limit(self.options.max_nodes, self.nodes, Limit::Nodes, mark)
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.depth -= 1;
+                Ok(())
+            }
+            Event::Scalar(..) | Event::Alias(_) => {
+                self.nodes += 1;
+                check_limit(self.options.max_nodes, self.nodes, Limit::Nodes, mark)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn load<R: MarkedEventReceiver>(
         &mut self,
         recv: &mut R,
@@ -177,19 +277,23 @@ impl<T: Iterator<Item = char>> Parser<T> {
     ) -> Result<(), ScanError> {
         if !self.scanner.stream_started() {
             let (ev, mark) = self.next()?;
-            assert_eq!(ev, Event::StreamStart);
-            recv.on_event(ev, mark);
+            match ev {
+                Event::StreamStart(_) => {}
+                _ => unreachable!(),
+            }
+            recv.on_spanned_event(ev, mark, self.span_end);
         }
 
         if self.scanner.stream_ended() {
             // XXX has parsed?
-            recv.on_event(Event::StreamEnd, self.scanner.mark());
+            let mark = self.scanner.mark();
+            recv.on_spanned_event(Event::StreamEnd, mark, mark);
             return Ok(());
         }
         loop {
             let (ev, mark) = self.next()?;
             if ev == Event::StreamEnd {
-                recv.on_event(ev, mark);
+                recv.on_spanned_event(ev, mark, self.span_end);
                 return Ok(());
             }
             // clear anchors before a new document
@@ -209,7 +313,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
         recv: &mut R,
     ) -> Result<(), ScanError> {
         assert_eq!(first_ev, Event::DocumentStart);
-        recv.on_event(first_ev, mark);
+        recv.on_spanned_event(first_ev, mark, self.span_end);
 
         let (ev, mark) = self.next()?;
         self.load_node(ev, mark, recv)?;
@@ -217,7 +321,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
         // DOCUMENT-END is expected.
         let (ev, mark) = self.next()?;
         assert_eq!(ev, Event::DocumentEnd);
-        recv.on_event(ev, mark);
+        recv.on_spanned_event(ev, mark, self.span_end);
 
         Ok(())
     }
@@ -230,15 +334,15 @@ impl<T: Iterator<Item = char>> Parser<T> {
     ) -> Result<(), ScanError> {
         match first_ev {
             Event::Alias(..) | Event::Scalar(..) => {
-                recv.on_event(first_ev, mark);
+                recv.on_spanned_event(first_ev, mark, self.span_end);
                 Ok(())
             }
-            Event::SequenceStart(_) => {
-                recv.on_event(first_ev, mark);
+            Event::SequenceStart(..) => {
+                recv.on_spanned_event(first_ev, mark, self.span_end);
                 self.load_sequence(recv)
             }
-            Event::MappingStart(_) => {
-                recv.on_event(first_ev, mark);
+            Event::MappingStart(..) => {
+                recv.on_spanned_event(first_ev, mark, self.span_end);
                 self.load_mapping(recv)
             }
             _ => {
@@ -263,7 +367,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             key_ev = ev;
             key_mark = mark;
         }
-        recv.on_event(key_ev, key_mark);
+        recv.on_spanned_event(key_ev, key_mark, self.span_end);
         Ok(())
     }
 
@@ -277,7 +381,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             ev = next_ev;
             mark = next_mark;
         }
-        recv.on_event(ev, mark);
+        recv.on_spanned_event(ev, mark, self.span_end);
         Ok(())
     }
 
@@ -323,10 +427,10 @@ impl<T: Iterator<Item = char>> Parser<T> {
 
     fn stream_start(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token(mark, TokenType::StreamStart(_)) => {
+            Token(mark, TokenType::StreamStart(encoding)) => {
                 self.state = State::ImplicitDocumentStart;
                 self.skip();
-                Ok((Event::StreamStart, mark))
+                Ok((Event::StreamStart(encoding), mark))
             }
             Token(mark, _) => Err(ScanError::new(mark, "did not find expected <stream-start>")),
         }
@@ -497,11 +601,12 @@ impl<T: Iterator<Item = char>> Parser<T> {
         match *self.peek_token()? {
             Token(mark, TokenType::BlockEntry) if indentless_sequence => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(_, TokenType::Scalar(..)) => {
                 self.pop_state();
                 if let Token(mark, TokenType::Scalar(style, v)) = self.fetch_token() {
+                    self.scalar_end = Some(self.scanner.scalar_end());
                     Ok((Event::Scalar(v, style, anchor_id, tag), mark))
                 } else {
                     unreachable!()
@@ -509,19 +614,19 @@ impl<T: Iterator<Item = char>> Parser<T> {
             }
             Token(mark, TokenType::FlowSequenceStart) => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::FlowMappingStart) => {
                 self.state = State::FlowMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                Ok((Event::MappingStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::BlockSequenceStart) if block => {
                 self.state = State::BlockSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::BlockMappingStart) if block => {
                 self.state = State::BlockMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                Ok((Event::MappingStart(anchor_id, tag), mark))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
             Token(mark, _) if tag.is_some() || anchor_id > 0 => {
@@ -718,7 +823,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             Token(mark, TokenType::Key) => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok((Event::MappingStart(0), mark))
+                Ok((Event::MappingStart(0, None), mark))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -832,6 +937,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
 #[cfg(test)]
 mod test {
     use super::{Event, Parser};
+    use crate::scanner::TEncoding;
 
     #[test]
     fn test_peek_eq_parse() {
@@ -855,4 +961,13 @@ a5: *x
             event.0 != Event::StreamEnd
         } {}
     }
+
+    #[test]
+    fn test_stream_start_encoding() {
+        let mut p = Parser::new("a".chars());
+        assert_eq!(p.next().unwrap().0, Event::StreamStart(TEncoding::Utf8));
+        let mut p = Parser::new("a".chars());
+        p.set_encoding(TEncoding::Utf16Le);
+        assert_eq!(p.next().unwrap().0, Event::StreamStart(TEncoding::Utf16Le));
+    }
 }
@@ -0,0 +1,419 @@
+//! [JSON Patch](https://tools.ietf.org/html/rfc6902) and
+//! [JSON Merge Patch](https://tools.ietf.org/html/rfc7396) for `Yaml`
+//! documents.
+
+use crate::pointer::{Pointer, PointerError};
+use crate::yaml::{Hash, Yaml};
+use std::error::Error;
+use std::fmt;
+
+/// An operation of a JSON Patch.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Operation {
+    /// Insert a value, or replace a mapping entry, see `Pointer::insert`.
+    Add { path: Pointer, value: Yaml },
+    /// Remove a value.
+    Remove { path: Pointer },
+    /// Replace a value, which must exist.
+    Replace { path: Pointer, value: Yaml },
+    /// Remove a value and add it at `path`.
+    Move { from: Pointer, path: Pointer },
+    /// Add a copy of a value at `path`.
+    Copy { from: Pointer, path: Pointer },
+    /// Check that the value at `path` equals `value`. Numbers are equal if
+    /// their values are, and mappings if they have the same entries in any
+    /// order.
+    Test { path: Pointer, value: Yaml },
+}
+
+impl Operation {
+    /// The name of the operation, such as `add`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Operation::Add { .. } => "add",
+            Operation::Remove { .. } => "remove",
+            Operation::Replace { .. } => "replace",
+            Operation::Move { .. } => "move",
+            Operation::Copy { .. } => "copy",
+            Operation::Test { .. } => "test",
+        }
+    }
+
+    /// The path the operation changes or tests.
+    pub fn path(&self) -> &Pointer {
+        match *self {
+            Operation::Add { ref path, .. }
+            | Operation::Remove { ref path }
+            | Operation::Replace { ref path, .. }
+            | Operation::Move { ref path, .. }
+            | Operation::Copy { ref path, .. }
+            | Operation::Test { ref path, .. } => path,
+        }
+    }
+
+    fn apply(&self, doc: &mut Yaml) -> Result<(), String> {
+        match *self {
+            Operation::Add {
+                ref path,
+                ref value,
+            } => {
+                path.insert(doc, value.clone()).map_err(from_pointer)?;
+            }
+            Operation::Remove { ref path } => {
+                path.remove(doc).map_err(from_pointer)?;
+            }
+            Operation::Replace {
+                ref path,
+                ref value,
+            } => {
+                *path.get_mut(doc).map_err(from_pointer)? = value.clone();
+            }
+            Operation::Move { ref from, ref path } => {
+                if from == path {
+                    // a no-op, but `from` must exist
+                    path.get(doc).map_err(from_pointer)?;
+                    return Ok(());
+                }
+                if path.segments().starts_with(from.segments()) {
+                    return Err(format!("cannot move `{}` into itself", from));
+                }
+                let value = from.remove(doc).map_err(from_pointer)?;
+                path.insert(doc, value).map_err(from_pointer)?;
+            }
+            Operation::Copy { ref from, ref path } => {
+                let value = from.get(doc).map_err(from_pointer)?.clone();
+                path.insert(doc, value).map_err(from_pointer)?;
+            }
+            Operation::Test {
+                ref path,
+                ref value,
+            } => {
+                if !equal(path.get(doc).map_err(from_pointer)?, value) {
+                    return Err("the value is not equal to the expected value".to_owned());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn from_pointer(e: PointerError) -> String {
+    e.to_string()
+}
+
+// Equality as JSON Patch defines it for `test`.
+fn equal(a: &Yaml, b: &Yaml) -> bool {
+    match (a, b) {
+        (Yaml::Array(a), Yaml::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Yaml::Hash(a), Yaml::Hash(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).map_or(false, |w| equal(v, w)))
+        }
+        (Yaml::Tagged(a_tag, a), Yaml::Tagged(b_tag, b)) => a_tag == b_tag && equal(a, b),
+        _ => match (a.as_i128(), b.as_i128()) {
+            (Some(a), Some(b)) => a == b,
+            _ => match (number(a), number(b)) {
+                (Some(a), Some(b)) => a == b,
+                _ => a == b,
+            },
+        },
+    }
+}
+
+fn number(v: &Yaml) -> Option<f64> {
+    v.as_i128().map(|i| i as f64).or_else(|| v.as_f64())
+}
+
+/// The error for a patch that is malformed or fails to apply.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct PatchError {
+    index: Option<usize>,
+    path: String,
+    info: String,
+}
+
+impl PatchError {
+    /// The index of the operation that failed, or `None` if the patch is
+    /// not a sequence of operations.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The `path` of the operation that failed, as written, or an empty
+    /// string if there is no such operation.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The error without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+}
+
+impl Error for PatchError {}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(
+                f,
+                "operation {} on `{}` failed: {}",
+                index, self.path, self.info
+            ),
+            None => write!(f, "invalid patch: {}", self.info),
+        }
+    }
+}
+
+/// Read a JSON Patch document: a sequence of mappings such as
+/// `{op: add, path: /a/0, value: 1}`.
+pub fn parse_patch(patch: &Yaml) -> Result<Vec<Operation>, PatchError> {
+    let ops = match *patch {
+        Yaml::Array(ref ops) => ops,
+        _ => {
+            return Err(PatchError {
+                index: None,
+                path: String::new(),
+                info: "expected a sequence of operations".to_owned(),
+            })
+        }
+    };
+    ops.iter()
+        .enumerate()
+        .map(|(index, op)| {
+            let path = op["path"].as_str().unwrap_or_default().to_owned();
+            parse_operation(op).map_err(|info| PatchError {
+                index: Some(index),
+                path,
+                info,
+            })
+        })
+        .collect()
+}
+
+fn parse_operation(op: &Yaml) -> Result<Operation, String> {
+    let hash = op.as_hash().ok_or("expected a mapping")?;
+    let field = |name: &str| -> Result<&Yaml, String> {
+        hash.get(&Yaml::String(name.to_owned()))
+            .ok_or_else(|| format!("missing `{}`", name))
+    };
+    let pointer = |name: &str| -> Result<Pointer, String> {
+        let text = field(name)?
+            .as_str()
+            .ok_or_else(|| format!("expected `{}` to be a string", name))?;
+        Pointer::parse(text).map_err(|e| e.to_string())
+    };
+    let name = field("op")?
+        .as_str()
+        .ok_or("expected `op` to be a string")?;
+    Ok(match name {
+        "add" => Operation::Add {
+            path: pointer("path")?,
+            value: field("value")?.clone(),
+        },
+        "remove" => Operation::Remove {
+            path: pointer("path")?,
+        },
+        "replace" => Operation::Replace {
+            path: pointer("path")?,
+            value: field("value")?.clone(),
+        },
+        "move" => Operation::Move {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        },
+        "copy" => Operation::Copy {
+            from: pointer("from")?,
+            path: pointer("path")?,
+        },
+        "test" => Operation::Test {
+            path: pointer("path")?,
+            value: field("value")?.clone(),
+        },
+        _ => return Err(format!("unknown operation `{}`", name)),
+    })
+}
+
+/// Apply operations in order. If one fails, `doc` is left unchanged.
+pub fn apply(doc: &mut Yaml, ops: &[Operation]) -> Result<(), PatchError> {
+    let mut patched = doc.clone();
+    for (index, op) in ops.iter().enumerate() {
+        op.apply(&mut patched).map_err(|info| PatchError {
+            index: Some(index),
+            path: op.path().to_string(),
+            info,
+        })?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+/// Read and apply a JSON Patch document, see `parse_patch` and `apply`.
+///
+/// ```
+/// use yaml_rust::patch::apply_patch;
+/// use yaml_rust::YamlLoader;
+///
+/// let load = |s| YamlLoader::load_from_str(s).unwrap().remove(0);
+/// let mut doc = load("{name: web, ports: [80]}");
+/// let patch = load("
+/// - {op: add, path: /ports/-, value: 443}
+/// - {op: replace, path: /name, value: api}
+/// ");
+/// apply_patch(&mut doc, &patch).unwrap();
+/// assert_eq!(doc, load("{name: api, ports: [80, 443]}"));
+///
+/// let err = apply_patch(&mut doc, &load("[{op: remove, path: /image}]")).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "operation 0 on `/image` failed: no entry for key `image` at `/image`"
+/// );
+/// ```
+pub fn apply_patch(doc: &mut Yaml, patch: &Yaml) -> Result<(), PatchError> {
+    apply(doc, &parse_patch(patch)?)
+}
+
+/// Apply a JSON Merge Patch: mappings in `patch` are merged into `doc`
+/// recursively, a null value removes an entry, and anything else replaces
+/// the value in `doc`. Existing entries keep their place, and new ones are
+/// appended.
+pub fn merge_patch(doc: &mut Yaml, patch: &Yaml) {
+    let patch = match *patch {
+        Yaml::Hash(ref patch) => patch,
+        _ => {
+            *doc = patch.clone();
+            return;
+        }
+    };
+    if doc.as_hash().is_none() {
+        *doc = Yaml::Hash(Hash::new());
+    }
+    for (k, v) in patch {
+        if v.is_null() {
+            if let Yaml::Hash(ref mut hash) = *doc {
+                hash.remove(k);
+            }
+        } else {
+            merge_patch(doc.entry(k.clone()).or_insert(Yaml::Null), v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut doc = load("{a: {b: [1, 2]}, c: x}");
+        let patch = load(
+            "
+- {op: test, path: /a/b/1, value: 2.0}
+- {op: add, path: /a/b/0, value: 0}
+- {op: remove, path: /a/b/2}
+- {op: replace, path: /c, value: [y]}
+- {op: copy, from: /c, path: /d}
+- {op: move, from: /a/b, path: /e}
+- {op: test, path: /a, value: {}}
+- {op: move, from: /e, path: /e}
+- {op: add, path: '', value: {doc: {k: 1, j: 2}}}
+- {op: test, path: /doc, value: {j: 2, k: 1}}
+",
+        );
+        apply_patch(&mut doc, &patch).unwrap();
+        assert_eq!(doc, load("{doc: {k: 1, j: 2}}"));
+
+        let mut doc = load("{a: {b: [1, 2]}, c: x}");
+        let patch = load("[{op: move, from: /c, path: /a/b/0}]");
+        apply_patch(&mut doc, &patch).unwrap();
+        assert_eq!(doc, load("{a: {b: [x, 1, 2]}}"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let original = load("{a: {b: [1, 2]}, c: x}");
+        let cases = [
+            (
+                "[{op: add, path: /c, value: 1}, {op: test, path: /c, value: 2}]",
+                "operation 1 on `/c` failed: the value is not equal to the expected value",
+            ),
+            (
+                "[{op: remove, path: /a/b/5}]",
+                "operation 0 on `/a/b/5` failed: index 5 is out of bounds for a sequence of length 2 at `/a/b/5`",
+            ),
+            (
+                "[{op: replace, path: /x, value: 1}]",
+                "operation 0 on `/x` failed: no entry for key `x` at `/x`",
+            ),
+            (
+                "[{op: move, from: /a, path: /a/b/0}]",
+                "operation 0 on `/a/b/0` failed: cannot move `/a` into itself",
+            ),
+            (
+                "[{op: remove, path: /c}, {op: jump, path: /c}]",
+                "operation 1 on `/c` failed: unknown operation `jump`",
+            ),
+            (
+                "[{op: add, path: /c}]",
+                "operation 0 on `/c` failed: missing `value`",
+            ),
+            (
+                "[{op: add, path: c, value: 1}]",
+                "operation 0 on `c` failed: expected the pointer to start with '/' in `c`",
+            ),
+            (
+                "{op: add}",
+                "invalid patch: expected a sequence of operations",
+            ),
+        ];
+        for &(patch, expected) in &cases {
+            let mut doc = original.clone();
+            let err = apply_patch(&mut doc, &load(patch)).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+            // a failed patch changes nothing
+            assert_eq!(doc, original);
+        }
+
+        let mut doc = original.clone();
+        let err = apply_patch(&mut doc, &load("[{op: add, path: /c}]")).unwrap_err();
+        assert_eq!(err.index(), Some(0));
+        let err = apply_patch(&mut doc, &load("{op: add}")).unwrap_err();
+        assert_eq!(err.index(), None);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        // the examples of RFC 7396 appendix A
+        let cases = [
+            ("{a: b}", "{a: c}", "{a: c}"),
+            ("{a: b}", "{b: c}", "{a: b, b: c}"),
+            ("{a: b}", "{a: ~}", "{}"),
+            ("{a: b, b: c}", "{a: ~}", "{b: c}"),
+            ("{a: [b]}", "{a: c}", "{a: c}"),
+            ("{a: c}", "{a: [b]}", "{a: [b]}"),
+            ("{a: {b: c}}", "{a: {b: d, c: ~}}", "{a: {b: d}}"),
+            ("{a: [{b: c}]}", "{a: [1]}", "{a: [1]}"),
+            ("[a, b]", "[c, d]", "[c, d]"),
+            ("{a: b}", "[c]", "[c]"),
+            ("{a: foo}", "~", "~"),
+            ("{a: foo}", "bar", "bar"),
+            ("{e: ~}", "{a: 1}", "{e: ~, a: 1}"),
+            ("[1, 2]", "{a: b, c: ~}", "{a: b}"),
+            ("{}", "{a: {bb: {ccc: ~}}}", "{a: {bb: {}}}"),
+        ];
+        for &(doc, patch, expected) in &cases {
+            let mut doc = load(doc);
+            merge_patch(&mut doc, &load(patch));
+            assert_eq!(doc, load(expected), "{}", patch);
+        }
+    }
+}
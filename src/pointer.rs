@@ -0,0 +1,522 @@
+//! Paths into a `Yaml` document in [JSON Pointer](https://tools.ietf.org/html/rfc6901)
+//! syntax.
+
+use crate::yaml::{kind, Hash, Yaml};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+
+/// A path to a node, such as `/spec/containers/0/image`.
+///
+/// Each segment is a mapping key or a sequence index. In the text form `~`
+/// is escaped as `~0` and `/` as `~1`, and the empty pointer refers to the
+/// whole document.
+///
+/// Mapping keys needn't be strings: a segment that isn't found as a string
+/// key is resolved like a plain scalar, so `/ports/80` finds the key `80`
+/// whether it was loaded as a string or an integer. Segments added with
+/// `push` are used as given.
+///
+/// ```
+/// use yaml_rust::pointer::Pointer;
+/// use yaml_rust::{Yaml, YamlLoader};
+///
+/// let mut doc = YamlLoader::load_from_str("a: {b: [1, 2]}").unwrap().remove(0);
+/// let p = Pointer::parse("/a/b/1").unwrap();
+/// assert_eq!(p.get(&doc).unwrap(), &Yaml::Integer(2));
+///
+/// *p.get_mut(&mut doc).unwrap() = Yaml::Integer(3);
+/// Pointer::parse("/a/b/-").unwrap().insert(&mut doc, Yaml::Integer(4)).unwrap();
+/// assert_eq!(doc["a"]["b"], YamlLoader::load_from_str("[1, 3, 4]").unwrap()[0]);
+///
+/// let err = Pointer::parse("/a/c/0").unwrap().get(&doc).unwrap_err();
+/// assert_eq!(err.to_string(), "no entry for key `c` at `/a/c`");
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug, Default, Eq, Ord, Hash)]
+pub struct Pointer {
+    segments: Vec<Yaml>,
+}
+
+/// The error for a pointer that is malformed or doesn't resolve.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct PointerError {
+    info: String,
+    pointer: String,
+    // the segment that failed, or `None` for a malformed pointer
+    segment: Option<usize>,
+    // the pointer up to and including the failing segment
+    at: String,
+}
+
+impl PointerError {
+    fn new(pointer: &Pointer, segment: usize, info: String) -> PointerError {
+        PointerError {
+            info,
+            pointer: pointer.to_string(),
+            segment: Some(segment),
+            at: Pointer {
+                segments: pointer.segments[..=segment].to_vec(),
+            }
+            .to_string(),
+        }
+    }
+
+    /// The error without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The whole pointer.
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    /// The index of the segment that failed to resolve, or `None` if the
+    /// pointer is malformed.
+    pub fn segment(&self) -> Option<usize> {
+        self.segment
+    }
+}
+
+impl Error for PointerError {}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.segment {
+            Some(_) => write!(f, "{} at `{}`", self.info, self.at),
+            None => write!(f, "{} in `{}`", self.info, self.pointer),
+        }
+    }
+}
+
+// The text of a segment, before escaping.
+fn segment_text(key: &Yaml) -> String {
+    match *key {
+        Yaml::String(ref s) | Yaml::Real(ref s) | Yaml::Timestamp(ref s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::BigInteger(i) => i.to_string(),
+        Yaml::Number(ref n) => n.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        Yaml::Null => "null".to_owned(),
+        ref key => format!("{:?}", key),
+    }
+}
+
+// The key in `hash` that `segment` refers to.
+pub(crate) fn find_key(hash: &Hash, segment: &Yaml) -> Option<Yaml> {
+    if hash.contains_key(segment) {
+        return Some(segment.clone());
+    }
+    let resolved = match *segment {
+        Yaml::String(ref s) => Yaml::from_str(s),
+        ref key => Yaml::String(segment_text(key)),
+    };
+    if hash.contains_key(&resolved) {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+// The index `segment` refers to, which in text must be a decimal integer
+// without leading zeros.
+pub(crate) fn array_index(segment: &Yaml) -> Option<usize> {
+    match *segment {
+        Yaml::String(ref s) if s == "0" || !s.starts_with('0') => {
+            if s.bytes().all(|b| b.is_ascii_digit()) {
+                s.parse().ok()
+            } else {
+                None
+            }
+        }
+        Yaml::Integer(i) => usize::try_from(i).ok(),
+        Yaml::Number(ref n) => n.as_i64().and_then(|i| usize::try_from(i).ok()),
+        _ => None,
+    }
+}
+
+// Tagged nodes are looked through, like `YamlEmitter` writes them.
+pub(crate) fn untag(node: &Yaml) -> &Yaml {
+    match *node {
+        Yaml::Tagged(_, ref inner) => untag(inner),
+        ref node => node,
+    }
+}
+
+pub(crate) fn untag_mut(node: &mut Yaml) -> &mut Yaml {
+    match *node {
+        Yaml::Tagged(_, ref mut inner) => untag_mut(inner),
+        ref mut node => node,
+    }
+}
+
+impl Pointer {
+    /// The pointer to the whole document.
+    pub fn root() -> Pointer {
+        Pointer::default()
+    }
+
+    /// Parse a pointer, which must be empty or start with `/`.
+    pub fn parse(s: &str) -> Result<Pointer, PointerError> {
+        let malformed = |info: &str| PointerError {
+            info: info.to_owned(),
+            pointer: s.to_owned(),
+            segment: None,
+            at: s.to_owned(),
+        };
+        if s.is_empty() {
+            return Ok(Pointer::root());
+        }
+        if !s.starts_with('/') {
+            return Err(malformed("expected the pointer to start with '/'"));
+        }
+        let mut segments = Vec::new();
+        for token in s[1..].split('/') {
+            let mut segment = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => match chars.next() {
+                        Some('0') => segment.push('~'),
+                        Some('1') => segment.push('/'),
+                        _ => return Err(malformed("found '~' not followed by '0' or '1'")),
+                    },
+                    c => segment.push(c),
+                }
+            }
+            segments.push(Yaml::String(segment));
+        }
+        Ok(Pointer { segments })
+    }
+
+    /// The segments of the pointer. Parsed segments are `Yaml::String`s.
+    pub fn segments(&self) -> &[Yaml] {
+        &self.segments
+    }
+
+    /// Append a segment, such as a mapping key of any type.
+    pub fn push(&mut self, segment: Yaml) {
+        self.segments.push(segment);
+    }
+
+    /// Remove the last segment, returning it.
+    pub fn pop(&mut self) -> Option<Yaml> {
+        self.segments.pop()
+    }
+
+    /// The pointer with another segment appended.
+    pub fn join(&self, segment: Yaml) -> Pointer {
+        let mut p = self.clone();
+        p.push(segment);
+        p
+    }
+
+    fn step<'a>(&self, node: &'a Yaml, i: usize) -> Result<&'a Yaml, PointerError> {
+        let segment = &self.segments[i];
+        match *untag(node) {
+            Yaml::Hash(ref hash) => match find_key(hash, segment) {
+                Some(key) => Ok(&hash[&key]),
+                None => Err(self.no_key(i)),
+            },
+            Yaml::Array(ref array) => match array_index(segment) {
+                Some(n) if n < array.len() => Ok(&array[n]),
+                Some(n) => Err(self.out_of_bounds(i, n, array.len())),
+                None => Err(self.not_an_index(i)),
+            },
+            ref node => Err(self.not_a_collection(i, node)),
+        }
+    }
+
+    fn step_mut<'a>(&self, node: &'a mut Yaml, i: usize) -> Result<&'a mut Yaml, PointerError> {
+        let segment = &self.segments[i];
+        match *untag_mut(node) {
+            Yaml::Hash(ref mut hash) => match find_key(hash, segment) {
+                Some(key) => Ok(hash.get_mut(&key).unwrap()),
+                None => Err(self.no_key(i)),
+            },
+            Yaml::Array(ref mut array) => match array_index(segment) {
+                Some(n) if n < array.len() => Ok(&mut array[n]),
+                Some(n) => Err(self.out_of_bounds(i, n, array.len())),
+                None => Err(self.not_an_index(i)),
+            },
+            ref node => Err(self.not_a_collection(i, node)),
+        }
+    }
+
+    fn no_key(&self, i: usize) -> PointerError {
+        let info = format!("no entry for key `{}`", segment_text(&self.segments[i]));
+        PointerError::new(self, i, info)
+    }
+
+    fn out_of_bounds(&self, i: usize, n: usize, len: usize) -> PointerError {
+        let info = format!(
+            "index {} is out of bounds for a sequence of length {}",
+            n, len
+        );
+        PointerError::new(self, i, info)
+    }
+
+    fn not_an_index(&self, i: usize) -> PointerError {
+        let info = format!(
+            "expected a sequence index, found `{}`",
+            segment_text(&self.segments[i])
+        );
+        PointerError::new(self, i, info)
+    }
+
+    fn not_a_collection(&self, i: usize, node: &Yaml) -> PointerError {
+        let info = format!("expected a mapping or a sequence, found a {}", kind(node));
+        PointerError::new(self, i, info)
+    }
+
+    /// The node the pointer refers to.
+    pub fn get<'a>(&self, doc: &'a Yaml) -> Result<&'a Yaml, PointerError> {
+        let mut node = doc;
+        for i in 0..self.segments.len() {
+            node = self.step(node, i)?;
+        }
+        Ok(node)
+    }
+
+    /// The node the pointer refers to, for editing in place.
+    pub fn get_mut<'a>(&self, doc: &'a mut Yaml) -> Result<&'a mut Yaml, PointerError> {
+        let mut node = doc;
+        for i in 0..self.segments.len() {
+            node = self.step_mut(node, i)?;
+        }
+        Ok(node)
+    }
+
+    // The parent of the node the pointer refers to, and the last segment.
+    fn parent_mut<'a>(&self, doc: &'a mut Yaml) -> Option<Result<&'a mut Yaml, PointerError>> {
+        let (_, parents) = self.segments.split_last()?;
+        let mut node = doc;
+        for i in 0..parents.len() {
+            node = match self.step_mut(node, i) {
+                Ok(node) => node,
+                Err(e) => return Some(Err(e)),
+            };
+        }
+        Some(Ok(untag_mut(node)))
+    }
+
+    /// Set the node the pointer refers to, in the manner of a JSON Patch
+    /// `add`: a mapping entry is inserted or replaced, a value is inserted
+    /// into a sequence before the given index, and the segment `-` appends
+    /// to a sequence. Returns the value that was replaced, if any.
+    ///
+    /// New mapping entries use the last segment as their key.
+    pub fn insert(&self, doc: &mut Yaml, value: Yaml) -> Result<Option<Yaml>, PointerError> {
+        let parent = match self.parent_mut(doc) {
+            Some(parent) => parent?,
+            None => return Ok(Some(mem::replace(doc, value))),
+        };
+        let i = self.segments.len() - 1;
+        let segment = &self.segments[i];
+        match *parent {
+            Yaml::Hash(ref mut hash) => {
+                let key = find_key(hash, segment);
+                match key {
+                    Some(key) => Ok(hash.insert(key, value)),
+                    None => {
+                        hash.insert(segment.clone(), value);
+                        Ok(None)
+                    }
+                }
+            }
+            Yaml::Array(ref mut array) => {
+                let n = match *segment {
+                    Yaml::String(ref s) if s == "-" => array.len(),
+                    ref segment => match array_index(segment) {
+                        Some(n) => n,
+                        None => return Err(self.not_an_index(i)),
+                    },
+                };
+                if n > array.len() {
+                    return Err(self.out_of_bounds(i, n, array.len()));
+                }
+                array.insert(n, value);
+                Ok(None)
+            }
+            ref node => Err(self.not_a_collection(i, node)),
+        }
+    }
+
+    /// Remove the node the pointer refers to from its mapping or sequence,
+    /// returning it. The root can't be removed.
+    pub fn remove(&self, doc: &mut Yaml) -> Result<Yaml, PointerError> {
+        let parent = match self.parent_mut(doc) {
+            Some(parent) => parent?,
+            None => {
+                return Err(PointerError {
+                    info: "cannot remove the whole document".to_owned(),
+                    pointer: String::new(),
+                    segment: None,
+                    at: String::new(),
+                })
+            }
+        };
+        let i = self.segments.len() - 1;
+        match *parent {
+            Yaml::Hash(ref mut hash) => match find_key(hash, &self.segments[i]) {
+                Some(key) => Ok(hash.remove(&key).unwrap()),
+                None => Err(self.no_key(i)),
+            },
+            Yaml::Array(ref mut array) => match array_index(&self.segments[i]) {
+                Some(n) if n < array.len() => Ok(array.remove(n)),
+                Some(n) => Err(self.out_of_bounds(i, n, array.len())),
+                None => Err(self.not_an_index(i)),
+            },
+            ref node => Err(self.not_a_collection(i, node)),
+        }
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Pointer, PointerError> {
+        Pointer::parse(s)
+    }
+}
+
+impl fmt::Display for Pointer {
+    /// Writes the pointer in JSON Pointer syntax, with non-string segments
+    /// written as plain scalars.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in &self.segments {
+            let text = segment_text(segment);
+            write!(f, "/{}", text.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    fn get<'a>(doc: &'a Yaml, p: &str) -> Result<&'a Yaml, String> {
+        Pointer::parse(p)
+            .and_then(|p| p.get(doc))
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let p = Pointer::parse("/a~1b/~0c/").unwrap();
+        let segments: Vec<_> = p.segments().iter().map(|s| s.as_str().unwrap()).collect();
+        assert_eq!(segments, ["a/b", "~c", ""]);
+        assert_eq!(p.to_string(), "/a~1b/~0c/");
+        assert!(Pointer::parse("").unwrap().segments().is_empty());
+
+        let err = Pointer::parse("a/b").unwrap_err();
+        assert_eq!(err.segment(), None);
+        assert_eq!(
+            err.to_string(),
+            "expected the pointer to start with '/' in `a/b`"
+        );
+        assert!(Pointer::parse("/a~2").is_err());
+        assert!(Pointer::parse("/a~").is_err());
+
+        let mut p = Pointer::root().join(Yaml::Integer(80));
+        p.push(Yaml::String("x/y".to_owned()));
+        assert_eq!(p.to_string(), "/80/x~1y");
+        assert_eq!(p.pop(), Some(Yaml::String("x/y".to_owned())));
+    }
+
+    #[test]
+    fn test_get() {
+        let doc = load(
+            "
+a:
+  b: [x, {c: 1}]
+  '': empty
+  a/b: slash
+80: int
+true: bool
+~: nothing
+'7': string
+tagged: !t {k: v}
+",
+        );
+        assert_eq!(get(&doc, "").unwrap(), &doc);
+        assert_eq!(get(&doc, "/a/b/1/c").unwrap(), &Yaml::Integer(1));
+        assert_eq!(get(&doc, "/a/").unwrap().as_str(), Some("empty"));
+        assert_eq!(get(&doc, "/a/a~1b").unwrap().as_str(), Some("slash"));
+        assert_eq!(get(&doc, "/80").unwrap().as_str(), Some("int"));
+        assert_eq!(get(&doc, "/true").unwrap().as_str(), Some("bool"));
+        assert_eq!(get(&doc, "/null").unwrap().as_str(), Some("nothing"));
+        assert_eq!(get(&doc, "/7").unwrap().as_str(), Some("string"));
+        assert_eq!(get(&doc, "/tagged/k").unwrap().as_str(), Some("v"));
+
+        // segments pushed as non-strings match string keys too
+        let p = Pointer::root().join(Yaml::Integer(7));
+        assert_eq!(p.get(&doc).unwrap().as_str(), Some("string"));
+
+        let cases = [
+            ("/a/c", "no entry for key `c` at `/a/c`", 1),
+            (
+                "/a/b/2",
+                "index 2 is out of bounds for a sequence of length 2 at `/a/b/2`",
+                2,
+            ),
+            (
+                "/a/b/01",
+                "expected a sequence index, found `01` at `/a/b/01`",
+                2,
+            ),
+            (
+                "/a/b/0/x",
+                "expected a mapping or a sequence, found a scalar at `/a/b/0/x`",
+                3,
+            ),
+        ];
+        for &(p, expected, segment) in &cases {
+            let err = Pointer::parse(p).unwrap().get(&doc).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+            assert_eq!(err.segment(), Some(segment));
+            assert_eq!(err.pointer(), p);
+        }
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut doc = load("{a: [1, 3], 80: x}");
+        let insert = |doc: &mut Yaml, p: &str, v: Yaml| Pointer::parse(p).unwrap().insert(doc, v);
+        let remove = |doc: &mut Yaml, p: &str| Pointer::parse(p).unwrap().remove(doc);
+
+        assert_eq!(insert(&mut doc, "/a/1", Yaml::Integer(2)), Ok(None));
+        assert_eq!(insert(&mut doc, "/a/-", Yaml::Integer(4)), Ok(None));
+        assert_eq!(insert(&mut doc, "/a/4", Yaml::Integer(5)), Ok(None));
+        assert!(insert(&mut doc, "/a/6", Yaml::Integer(7)).is_err());
+        assert_eq!(
+            insert(&mut doc, "/80", Yaml::Null),
+            Ok(Some(Yaml::String("x".to_owned())))
+        );
+        assert_eq!(insert(&mut doc, "/b", Yaml::Null), Ok(None));
+        assert_eq!(doc, load("{a: [1, 2, 3, 4, 5], 80: ~, b: ~}"));
+
+        assert_eq!(remove(&mut doc, "/a/0"), Ok(Yaml::Integer(1)));
+        assert_eq!(remove(&mut doc, "/80"), Ok(Yaml::Null));
+        assert_eq!(doc, load("{a: [2, 3, 4, 5], b: ~}"));
+        let err = remove(&mut doc, "/c/d").unwrap_err();
+        assert_eq!(err.segment(), Some(0));
+        assert!(remove(&mut doc, "/b/0").is_err());
+        assert!(remove(&mut doc, "").is_err());
+
+        *Pointer::parse("/a/0").unwrap().get_mut(&mut doc).unwrap() = Yaml::Null;
+        assert_eq!(
+            insert(&mut doc, "", Yaml::Integer(1)),
+            Ok(Some(load("{a: [~, 3, 4, 5], b: ~}")))
+        );
+        assert_eq!(doc, Yaml::Integer(1));
+    }
+}
@@ -0,0 +1,843 @@
+//! JSONPath queries over `Yaml` documents.
+
+use crate::number::Number;
+use crate::pointer::{find_key, untag, Pointer};
+use crate::yaml::{parse_f64, Yaml};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A compiled JSONPath expression, such as `$.spec.containers[*].image`.
+///
+/// The syntax follows [RFC 9535](https://www.rfc-editor.org/rfc/rfc9535):
+///
+/// * `$` is the document, and `@` the current node in a filter.
+/// * `.name` and `['name']` select a mapping entry. Like `Pointer`
+///   segments, names that aren't found as string keys are resolved like
+///   plain scalars, so `.80` finds the integer key `80`.
+/// * `.*` and `[*]` select every entry of a mapping or sequence.
+/// * `[1]` selects a sequence element, and `[-1]` counts from the end.
+/// * `[start:end:step]` selects a slice of a sequence, where each part is
+///   optional.
+/// * `[a, 'b', 0]` selects the union of several selectors.
+/// * `..` applies the selector after it to a node and all of its
+///   descendants, as in `$..name` or `$..[0]`.
+/// * `[?(filter)]`, or `[?filter]`, selects the entries for which the
+///   filter holds. A filter compares paths from `@` or `$` with `==`, `!=`,
+///   `<`, `<=`, `>` or `>=` against each other or against string, number,
+///   `true`, `false` and `null` literals, tests that a path exists, and
+///   combines those with `&&`, `||`, `!` and parentheses.
+///
+/// Numbers compare by value whatever their type, and strings by their
+/// characters. Values of other types are only equal to an equal value. A
+/// path that doesn't select exactly one node is only equal to another such
+/// path, and is neither less nor greater than anything. Tagged nodes are
+/// looked through.
+///
+/// ```
+/// use yaml_rust::query::Query;
+/// use yaml_rust::YamlLoader;
+///
+/// let doc = YamlLoader::load_from_str("
+/// spec:
+///   containers:
+///     - {name: web, image: nginx}
+///     - {name: log, image: fluentd}
+/// ").unwrap().remove(0);
+///
+/// let query = Query::compile("$.spec.containers[?(@.name != 'log')].image").unwrap();
+/// let matches = query.select(&doc);
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].0.to_string(), "/spec/containers/0/image");
+/// assert_eq!(matches[0].1.as_str(), Some("nginx"));
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+/// The error for a malformed query.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct QueryError {
+    info: String,
+    pos: usize,
+}
+
+impl QueryError {
+    /// The error without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The offset in characters where the error was found.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl Error for QueryError {}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.info, self.pos + 1)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Segment {
+    descendants: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Selector {
+    Name(Yaml),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Filter),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(Operand),
+    Compare(Operand, Op, Operand),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Operand {
+    // a path from `@`, or from `$` if the flag is set
+    Path(bool, Vec<Segment>),
+    Literal(Yaml),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Query {
+    /// Compile a query, which must start with `$`.
+    pub fn compile(expr: &str) -> Result<Query, QueryError> {
+        let mut p = QueryParser {
+            chars: expr.chars().collect(),
+            pos: 0,
+        };
+        if !p.eat('$') {
+            return Err(p.error("expected the query to start with '$'"));
+        }
+        let segments = p.segments()?;
+        if p.pos < p.chars.len() {
+            return Err(p.unexpected());
+        }
+        Ok(Query { segments })
+    }
+
+    /// The nodes the query selects in `doc`, in document order, each with
+    /// its path from the root.
+    pub fn select<'a>(&self, doc: &'a Yaml) -> Vec<(Pointer, &'a Yaml)> {
+        select(&self.segments, Pointer::root(), doc, doc)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Query, QueryError> {
+        Query::compile(s)
+    }
+}
+
+fn select<'a>(
+    segments: &[Segment],
+    path: Pointer,
+    node: &'a Yaml,
+    root: &'a Yaml,
+) -> Vec<(Pointer, &'a Yaml)> {
+    let mut nodes = vec![(path, node)];
+    for segment in segments {
+        let mut out = Vec::new();
+        for (path, node) in nodes {
+            if segment.descendants {
+                descend(segment, path, node, root, &mut out);
+            } else {
+                for selector in &segment.selectors {
+                    selector.select(&path, node, root, &mut out);
+                }
+            }
+        }
+        nodes = out;
+    }
+    nodes
+}
+
+// Applies the selectors of `segment` to `node` and then to each of its
+// descendants.
+fn descend<'a>(
+    segment: &Segment,
+    path: Pointer,
+    node: &'a Yaml,
+    root: &'a Yaml,
+    out: &mut Vec<(Pointer, &'a Yaml)>,
+) {
+    for selector in &segment.selectors {
+        selector.select(&path, node, root, out);
+    }
+    for (child_path, child) in children(&path, node) {
+        descend(segment, child_path, child, root, out);
+    }
+}
+
+fn children<'a>(path: &Pointer, node: &'a Yaml) -> Vec<(Pointer, &'a Yaml)> {
+    match *untag(node) {
+        Yaml::Hash(ref hash) => hash
+            .iter()
+            .map(|(k, v)| (path.join(k.clone()), v))
+            .collect(),
+        Yaml::Array(ref array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (path.join(Yaml::Integer(i as i64)), v))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl Selector {
+    fn select<'a>(
+        &self,
+        path: &Pointer,
+        node: &'a Yaml,
+        root: &'a Yaml,
+        out: &mut Vec<(Pointer, &'a Yaml)>,
+    ) {
+        match (self, untag(node)) {
+            (Selector::Name(name), Yaml::Hash(hash)) => {
+                if let Some(key) = find_key(hash, name) {
+                    let value = &hash[&key];
+                    out.push((path.join(key), value));
+                }
+            }
+            (Selector::Wildcard, node) => out.extend(children(path, node)),
+            (Selector::Index(i), Yaml::Array(array)) => {
+                let len = array.len() as i64;
+                let i = if *i < 0 { len + i } else { *i };
+                if 0 <= i && i < len {
+                    out.push((path.join(Yaml::Integer(i)), &array[i as usize]));
+                }
+            }
+            (Selector::Slice(start, end, step), Yaml::Array(array)) => {
+                for i in slice(array.len() as i64, *start, *end, step.unwrap_or(1)) {
+                    out.push((path.join(Yaml::Integer(i)), &array[i as usize]));
+                }
+            }
+            (Selector::Filter(filter), node) => out.extend(
+                children(path, node)
+                    .into_iter()
+                    .filter(|&(_, child)| filter.test(child, root)),
+            ),
+            _ => {}
+        }
+    }
+}
+
+// The indices of a slice, following the rules of RFC 9535 section 2.3.4.2.
+fn slice(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<i64> {
+    let normalize = |i: i64| if i < 0 { len + i } else { i };
+    let mut indices = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        let mut i = lower;
+        while i < upper {
+            indices.push(i);
+            i = match i.checked_add(step) {
+                Some(i) => i,
+                None => break,
+            };
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).max(-1).min(len - 1);
+        let mut i = upper;
+        while lower < i {
+            indices.push(i);
+            i = match i.checked_add(step) {
+                Some(i) => i,
+                None => break,
+            };
+        }
+    }
+    indices
+}
+
+impl Filter {
+    fn test(&self, node: &Yaml, root: &Yaml) -> bool {
+        match *self {
+            Filter::Or(ref a, ref b) => a.test(node, root) || b.test(node, root),
+            Filter::And(ref a, ref b) => a.test(node, root) && b.test(node, root),
+            Filter::Not(ref f) => !f.test(node, root),
+            Filter::Exists(ref path) => !path.values(node, root).is_empty(),
+            Filter::Compare(ref a, op, ref b) => {
+                let (a, b) = (a.value(node, root), b.value(node, root));
+                let equal = match (a, b) {
+                    (Some(a), Some(b)) => equal(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                };
+                let ordering = match (a, b) {
+                    (Some(a), Some(b)) => compare(a, b),
+                    _ => None,
+                };
+                match op {
+                    Op::Eq => equal,
+                    Op::Ne => !equal,
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    Op::Le => equal || ordering == Some(Ordering::Less),
+                    Op::Ge => equal || ordering == Some(Ordering::Greater),
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn values<'a>(&'a self, node: &'a Yaml, root: &'a Yaml) -> Vec<&'a Yaml> {
+        match *self {
+            Operand::Path(from_root, ref segments) => {
+                let start = if from_root { root } else { node };
+                select(segments, Pointer::root(), start, root)
+                    .into_iter()
+                    .map(|(_, v)| v)
+                    .collect()
+            }
+            Operand::Literal(ref v) => vec![v],
+        }
+    }
+
+    // The single value of the operand, if it has exactly one.
+    fn value<'a>(&'a self, node: &'a Yaml, root: &'a Yaml) -> Option<&'a Yaml> {
+        let values = self.values(node, root);
+        if values.len() == 1 {
+            Some(values[0])
+        } else {
+            None
+        }
+    }
+}
+
+fn number(v: &Yaml) -> Option<Number> {
+    match *v {
+        Yaml::Integer(i) => Some(Number::from(i)),
+        Yaml::BigInteger(i) => Number::with_radix(i, 10),
+        Yaml::Real(ref s) => parse_f64(s).map(Number::from),
+        Yaml::Number(ref n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+fn equal(a: &Yaml, b: &Yaml) -> bool {
+    match (untag(a), untag(b)) {
+        (a, b) if number(a).is_some() && number(b).is_some() => {
+            compare(a, b) == Some(Ordering::Equal)
+        }
+        (a, b) => a == b,
+    }
+}
+
+// Numbers compare with numbers and strings with strings.
+fn compare(a: &Yaml, b: &Yaml) -> Option<Ordering> {
+    let (a, b) = (untag(a), untag(b));
+    if let (Some(a), Some(b)) = (number(a), number(b)) {
+        return match (a.as_i128(), b.as_i128()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.as_f64().partial_cmp(&b.as_f64()),
+        };
+    }
+    match (a, b) {
+        (Yaml::String(a), Yaml::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+struct QueryParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl QueryParser {
+    fn error(&self, info: &str) -> QueryError {
+        QueryError {
+            info: info.to_owned(),
+            pos: self.pos,
+        }
+    }
+
+    fn unexpected(&self) -> QueryError {
+        match self.peek() {
+            Some(c) => self.error(&format!("unexpected character '{}'", c)),
+            None => self.error("unexpected end of query"),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let n = s.chars().count();
+        if self.chars[self.pos..].iter().take(n).cloned().eq(s.chars()) {
+            self.pos += n;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    // The segments after `$` or `@`.
+    fn segments(&mut self) -> Result<Vec<Segment>, QueryError> {
+        let mut segments = Vec::new();
+        loop {
+            let descendants = if self.eat_str("..") {
+                true
+            } else if self.eat('.') {
+                false
+            } else if self.peek() == Some('[') {
+                segments.push(Segment {
+                    descendants: false,
+                    selectors: self.bracket()?,
+                });
+                continue;
+            } else {
+                return Ok(segments);
+            };
+            let selectors = match self.peek() {
+                Some('[') if descendants => self.bracket()?,
+                Some('*') => {
+                    self.pos += 1;
+                    vec![Selector::Wildcard]
+                }
+                _ => vec![Selector::Name(Yaml::String(self.name()?))],
+            };
+            segments.push(Segment {
+                descendants,
+                selectors,
+            });
+        }
+    }
+
+    fn name(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '-' || !c.is_ascii() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn bracket(&mut self) -> Result<Vec<Selector>, QueryError> {
+        self.expect('[')?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_spaces();
+            selectors.push(self.selector()?);
+            self.skip_spaces();
+            if self.eat(']') {
+                return Ok(selectors);
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, QueryError> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Selector::Name(Yaml::String(self.string()?))),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.skip_spaces();
+                Ok(Selector::Filter(self.or()?))
+            }
+            _ => {
+                let start = self.integer()?;
+                self.skip_spaces();
+                if !self.eat(':') {
+                    return match start {
+                        Some(i) => Ok(Selector::Index(i)),
+                        None => Err(self.unexpected()),
+                    };
+                }
+                self.skip_spaces();
+                let end = self.integer()?;
+                self.skip_spaces();
+                let step = if self.eat(':') {
+                    self.skip_spaces();
+                    self.integer()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, QueryError> {
+        let start = self.pos;
+        self.eat('-');
+        while let Some('0'..='9') = self.peek() {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.as_str() {
+            "" => Ok(None),
+            _ => match digits.parse() {
+                Ok(i) => Ok(Some(i)),
+                Err(_) => {
+                    self.pos = start;
+                    Err(self.error("expected an integer"))
+                }
+            },
+        }
+    }
+
+    fn string(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        let quote = self.chars[self.pos];
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    self.pos = start;
+                    return Err(self.error("found an unterminated string"));
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c @ '\\') | Some(c @ '\'') | Some(c @ '"') | Some(c @ '/') => {
+                            s.push(c)
+                        }
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        _ => return Err(self.error("found an unknown escape")),
+                    }
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    s.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.and()?;
+        while self.eat_str("||") {
+            self.skip_spaces();
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, QueryError> {
+        let mut filter = self.unary()?;
+        while self.eat_str("&&") {
+            self.skip_spaces();
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, QueryError> {
+        let filter = if self.eat('!') {
+            self.skip_spaces();
+            Filter::Not(Box::new(self.unary()?))
+        } else if self.eat('(') {
+            self.skip_spaces();
+            let filter = self.or()?;
+            self.expect(')')?;
+            filter
+        } else {
+            self.comparison()?
+        };
+        self.skip_spaces();
+        Ok(filter)
+    }
+
+    fn comparison(&mut self) -> Result<Filter, QueryError> {
+        let start = self.pos;
+        let a = self.operand()?;
+        self.skip_spaces();
+        let op = if self.eat_str("==") {
+            Op::Eq
+        } else if self.eat_str("!=") {
+            Op::Ne
+        } else if self.eat_str("<=") {
+            Op::Le
+        } else if self.eat_str(">=") {
+            Op::Ge
+        } else if self.eat('<') {
+            Op::Lt
+        } else if self.eat('>') {
+            Op::Gt
+        } else {
+            return match a {
+                Operand::Path(..) => Ok(Filter::Exists(a)),
+                Operand::Literal(_) => {
+                    self.pos = start;
+                    Err(self.error("expected a path or a comparison"))
+                }
+            };
+        };
+        self.skip_spaces();
+        let b = self.operand()?;
+        Ok(Filter::Compare(a, op, b))
+    }
+
+    fn operand(&mut self) -> Result<Operand, QueryError> {
+        match self.peek() {
+            Some('@') | Some('$') => {
+                let from_root = self.peek() == Some('$');
+                self.pos += 1;
+                Ok(Operand::Path(from_root, self.segments()?))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Literal(Yaml::String(self.string()?))),
+            Some('-') | Some('0'..='9') => {
+                let start = self.pos;
+                while let Some('-') | Some('+') | Some('.') | Some('e') | Some('E')
+                | Some('0'..='9') = self.peek()
+                {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                match Yaml::from_str(&text) {
+                    v @ Yaml::Integer(_) | v @ Yaml::Real(_) => Ok(Operand::Literal(v)),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("expected a number"))
+                    }
+                }
+            }
+            _ => {
+                if self.eat_str("true") {
+                    Ok(Operand::Literal(Yaml::Boolean(true)))
+                } else if self.eat_str("false") {
+                    Ok(Operand::Literal(Yaml::Boolean(false)))
+                } else if self.eat_str("null") {
+                    Ok(Operand::Literal(Yaml::Null))
+                } else {
+                    Err(self.unexpected())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn paths(expr: &str, doc: &Yaml) -> Vec<String> {
+        Query::compile(expr)
+            .unwrap()
+            .select(doc)
+            .iter()
+            .map(|(path, _)| path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let doc = YamlLoader::load_from_str(
+            "
+store:
+  book:
+    - {title: A, price: 8.95, tags: [x]}
+    - {title: B, price: 12}
+    - {title: C, price: 22.5, isbn: '123'}
+  bicycle: {color: red, price: 19.95}
+80: port
+",
+        )
+        .unwrap()
+        .remove(0);
+
+        assert_eq!(paths("$", &doc), [""]);
+        assert_eq!(
+            paths("$.store.bicycle.color", &doc),
+            ["/store/bicycle/color"]
+        );
+        assert_eq!(paths("$['store']['bicycle']", &doc), ["/store/bicycle"]);
+        assert_eq!(paths("$.80", &doc), ["/80"]);
+        assert_eq!(
+            paths("$.store.book[*].title", &doc),
+            [
+                "/store/book/0/title",
+                "/store/book/1/title",
+                "/store/book/2/title"
+            ]
+        );
+        assert_eq!(paths("$.store.*", &doc), ["/store/book", "/store/bicycle"]);
+        assert_eq!(
+            paths("$..price", &doc),
+            [
+                "/store/book/0/price",
+                "/store/book/1/price",
+                "/store/book/2/price",
+                "/store/bicycle/price"
+            ]
+        );
+        assert_eq!(
+            paths("$..[0]", &doc),
+            ["/store/book/0", "/store/book/0/tags/0"]
+        );
+        assert_eq!(
+            paths("$.store.book[-1].title", &doc),
+            ["/store/book/2/title"]
+        );
+        assert_eq!(paths("$.store.book[5]", &doc), Vec::<String>::new());
+        assert_eq!(
+            paths("$.store.book[0, 2]['title', 'isbn']", &doc),
+            [
+                "/store/book/0/title",
+                "/store/book/2/title",
+                "/store/book/2/isbn"
+            ]
+        );
+        assert_eq!(paths("$.missing.x", &doc), Vec::<String>::new());
+
+        let m = Query::compile("$.store.bicycle.price")
+            .unwrap()
+            .select(&doc);
+        assert_eq!(m[0].1, &Yaml::Real("19.95".to_owned()));
+        assert_eq!(m[0].0.get(&doc).unwrap(), m[0].1);
+    }
+
+    #[test]
+    fn test_slices() {
+        let doc = YamlLoader::load_from_str("[0, 1, 2, 3, 4]")
+            .unwrap()
+            .remove(0);
+        let values = |expr: &str| -> Vec<i64> {
+            Query::compile(expr)
+                .unwrap()
+                .select(&doc)
+                .iter()
+                .map(|(_, v)| v.as_i64().unwrap())
+                .collect()
+        };
+        assert_eq!(values("$[1:3]"), [1, 2]);
+        assert_eq!(values("$[:2]"), [0, 1]);
+        assert_eq!(values("$[-2:]"), [3, 4]);
+        assert_eq!(values("$[::2]"), [0, 2, 4]);
+        assert_eq!(values("$[::-1]"), [4, 3, 2, 1, 0]);
+        assert_eq!(values("$[3:0:-2]"), [3, 1]);
+        assert_eq!(values("$[1:10]"), [1, 2, 3, 4]);
+        assert_eq!(values("$[::0]"), Vec::<i64>::new());
+        assert_eq!(values("$[1::9223372036854775807]"), [1]);
+        assert_eq!(values("$[::-9223372036854775808]"), [4]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let doc = YamlLoader::load_from_str(
+            "
+- {kind: Secret, name: a, size: 3}
+- {kind: ConfigMap, name: b, size: 10, labels: {tier: web}}
+- {kind: Secret, name: c, size: 2.5, data: !t {k: v}}
+- [{kind: Secret, name: d}]
+",
+        )
+        .unwrap()
+        .remove(0);
+        let names = |expr: &str| -> Vec<String> {
+            Query::compile(expr)
+                .unwrap()
+                .select(&doc)
+                .iter()
+                .map(|(_, v)| v["name"].as_str().unwrap_or("?").to_owned())
+                .collect()
+        };
+        assert_eq!(names("$[?(@.kind == 'Secret')]"), ["a", "c"]);
+        assert_eq!(names("$..[?(@.kind == \"Secret\")]"), ["a", "c", "d"]);
+        assert_eq!(names("$[?@.size > 2.5]"), ["a", "b"]);
+        assert_eq!(
+            names("$[?(@.size <= 3 && @.kind != 'ConfigMap')]"),
+            ["a", "c"]
+        );
+        assert_eq!(names("$[?(@.labels || @.data.k == 'v')]"), ["b", "c"]);
+        assert_eq!(names("$[?(!@.size)]"), ["?"]);
+        assert_eq!(names("$[?(@.labels.tier == 'web')]"), ["b"]);
+        assert_eq!(names("$[?(@.size == $[0].size)]"), ["a"]);
+        assert_eq!(names("$[?(@.name < 'b')]"), ["a"]);
+        // strings and numbers don't compare
+        assert_eq!(names("$[?(@.size < 'z')]"), Vec::<String>::new());
+        assert_eq!(names("$[?(@.missing != 1)]"), ["a", "b", "c", "?"]);
+        assert_eq!(names("$[?(@.missing == @.other)]"), ["a", "b", "c", "?"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("store", "expected the query to start with '$' at column 1"),
+            ("$.", "expected a name at column 3"),
+            ("$[1", "expected ',' or ']' at column 4"),
+            ("$['a]", "found an unterminated string at column 3"),
+            ("$[?(@.a == )]", "unexpected character ')' at column 12"),
+            ("$[?('a')]", "expected a path or a comparison at column 5"),
+            ("$.a b", "unexpected character ' ' at column 4"),
+        ];
+        for &(expr, expected) in &cases {
+            assert_eq!(Query::compile(expr).unwrap_err().to_string(), expected);
+        }
+    }
+}
@@ -2,9 +2,14 @@ use std::collections::VecDeque;
 use std::error::Error;
 use std::{char, fmt};
 
+/// The encoding of a stream, as reported by `Event::StreamStart`.
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
     Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
@@ -26,7 +31,7 @@ pub struct Marker {
 }
 
 impl Marker {
-    fn new(index: usize, line: usize, col: usize) -> Marker {
+    pub(crate) fn new(index: usize, line: usize, col: usize) -> Marker {
         Marker { index, line, col }
     }
 
@@ -43,10 +48,34 @@ impl Marker {
     }
 }
 
+/// A resource limit, see `ParserOptions` and `LoaderOptions`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Limit {
+    Depth,
+    ScalarLength,
+    Nodes,
+    Documents,
+    AliasExpansion,
+}
+
+impl Limit {
+    fn description(self) -> &'static str {
+        match self {
+            Limit::Depth => "nesting depth limit exceeded",
+            Limit::ScalarLength => "scalar length limit exceeded",
+            Limit::Nodes => "node count limit exceeded",
+            Limit::Documents => "document count limit exceeded",
+            Limit::AliasExpansion => "alias expansion limit exceeded",
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct ScanError {
     mark: Marker,
     info: String,
+    limit: Option<Limit>,
+    original: Option<Marker>,
 }
 
 impl ScanError {
@@ -54,12 +83,55 @@ impl ScanError {
         ScanError {
             mark: loc,
             info: info.to_owned(),
+            limit: None,
+            original: None,
+        }
+    }
+
+    /// Create the error reported when the input exceeds a resource limit.
+    pub fn limit_exceeded(loc: Marker, limit: Limit) -> ScanError {
+        ScanError {
+            mark: loc,
+            info: limit.description().to_owned(),
+            limit: Some(limit),
+            original: None,
+        }
+    }
+
+    /// Create the error reported when a mapping key at `loc` repeats the
+    /// key at `original`.
+    pub fn duplicate_key(loc: Marker, original: Marker) -> ScanError {
+        ScanError {
+            mark: loc,
+            info: format!(
+                "duplicate key, first defined at line {} column {}",
+                original.line,
+                original.col + 1
+            ),
+            limit: None,
+            original: Some(original),
         }
     }
 
     pub fn marker(&self) -> &Marker {
         &self.mark
     }
+
+    /// The description of the error, without its location.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The limit that was exceeded, if this error was caused by one.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+
+    /// The position of the first occurrence of a duplicated key, if this
+    /// error was caused by one.
+    pub fn original_marker(&self) -> Option<&Marker> {
+        self.original.as_ref()
+    }
 }
 
 impl Error for ScanError {
@@ -143,6 +215,11 @@ pub struct Scanner<T> {
     tokens: VecDeque<Token>,
     buffer: VecDeque<char>,
     error: Option<ScanError>,
+    // end markers of the scalar tokens still in `tokens`, in order
+    scalar_ends: VecDeque<Marker>,
+    scalar_end: Marker,
+    max_scalar_length: Option<usize>,
+    encoding: TEncoding,
 
     stream_start_produced: bool,
     stream_end_produced: bool,
@@ -236,6 +313,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             mark: Marker::new(0, 1, 0),
             tokens: VecDeque::new(),
             error: None,
+            scalar_ends: VecDeque::new(),
+            scalar_end: Marker::new(0, 1, 0),
+            max_scalar_length: None,
+            encoding: TEncoding::Utf8,
 
             stream_start_produced: false,
             stream_end_produced: false,
@@ -249,6 +330,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             token_available: false,
         }
     }
+    /// The encoding reported by the `StreamStart` token, for input that was
+    /// decoded from bytes before it reached the scanner.
+    pub fn set_encoding(&mut self, encoding: TEncoding) {
+        self.encoding = encoding;
+    }
+    /// Fail with a `Limit::ScalarLength` error on scalars longer than `max`
+    /// bytes.
+    pub fn set_max_scalar_length(&mut self, max: Option<usize>) {
+        self.max_scalar_length = max;
+    }
     #[inline]
     pub fn get_error(&self) -> Option<ScanError> {
         match self.error {
@@ -312,6 +403,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     pub fn mark(&self) -> Marker {
         self.mark
     }
+    /// The marker just past the last scalar token returned by `next_token`.
+    #[inline]
+    pub fn scalar_end(&self) -> Marker {
+        self.scalar_end
+    }
     #[inline]
     fn read_break(&mut self, s: &mut String) {
         if self.buffer[0] == '\r' && self.buffer[1] == '\n' {
@@ -325,6 +421,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             unreachable!();
         }
     }
+    #[inline]
+    fn check_scalar_length(&self, string: &str, mark: &Marker) -> ScanResult {
+        match self.max_scalar_length {
+            Some(max) if string.len() > max => {
+                Err(ScanError::limit_exceeded(*mark, Limit::ScalarLength))
+            }
+            _ => Ok(()),
+        }
+    }
     fn insert_token(&mut self, pos: usize, tok: Token) {
         let old_len = self.tokens.len();
         assert!(pos <= old_len);
@@ -437,6 +542,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.token_available = false;
         self.tokens_parsed += 1;
 
+        if let TokenType::Scalar(..) = t.1 {
+            self.scalar_end = self.scalar_ends.pop_front().unwrap();
+        }
+
         if let TokenType::StreamEnd = t.1 {
             self.stream_end_produced = true;
         }
@@ -514,7 +623,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.stream_start_produced = true;
         self.allow_simple_key();
         self.tokens
-            .push_back(Token(mark, TokenType::StreamStart(TEncoding::Utf8)));
+            .push_back(Token(mark, TokenType::StreamStart(self.encoding)));
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
     }
 
@@ -1192,6 +1301,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 self.skip();
                 self.lookahead(1);
             }
+            self.check_scalar_length(&string, &start_mark)?;
             // break on EOF
             if is_z(self.ch()) {
                 break;
@@ -1212,7 +1322,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if chomping == 1 {
             string.push_str(&trailing_breaks);
         }
+        self.check_scalar_length(&string, &start_mark)?;
 
+        self.scalar_ends.push_back(self.mark);
         if literal {
             Ok(Token(
                 start_mark,
@@ -1322,6 +1434,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // Consume non-blank characters.
 
             while !is_blankz(self.ch()) {
+                self.check_scalar_length(&string, &start_mark)?;
                 match self.ch() {
                     // Check for an escaped single quote.
                     '\'' if self.buffer[1] == '\'' && single => {
@@ -1462,8 +1575,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         } // loop
 
+        self.check_scalar_length(&string, &start_mark)?;
+
         // Eat the right quote.
         self.skip();
+        self.scalar_ends.push_back(self.mark);
 
         if single {
             Ok(Token(
@@ -1491,6 +1607,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn scan_plain_scalar(&mut self) -> Result<Token, ScanError> {
         let indent = self.indent + 1;
         let start_mark = self.mark;
+        let mut end_mark = start_mark;
 
         let mut string = String::new();
         let mut leading_break = String::new();
@@ -1552,6 +1669,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
                 string.push(self.ch());
                 self.skip();
+                end_mark = self.mark;
+                self.check_scalar_length(&string, &start_mark)?;
                 self.lookahead(2);
             }
             // is the end?
@@ -1599,6 +1718,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.allow_simple_key();
         }
 
+        self.scalar_ends.push_back(end_mark);
         Ok(Token(
             start_mark,
             TokenType::Scalar(TScalarStyle::Plain, string),
@@ -1650,7 +1770,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
-                start_mark,
+                sk.mark,
             );
 
             self.simple_keys.last_mut().unwrap().possible = false;
@@ -0,0 +1,402 @@
+//! Rules for resolving the type of untagged plain scalars.
+
+use crate::yaml::{parse_integer, Yaml};
+
+/// A set of rules that decides whether an untagged plain scalar is a null,
+/// a boolean, a number or a string.
+///
+/// `YamlLoader` uses `Yaml::from_str` unless a schema is selected in
+/// `LoaderOptions::schema`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Schema {
+    /// The YAML 1.2 failsafe schema: every scalar is a string.
+    Failsafe,
+    /// The YAML 1.2 JSON schema: only `null`, `true`, `false` and JSON
+    /// numbers are resolved, anything else is a string.
+    Json,
+    /// The YAML 1.2 core schema.
+    Core,
+    /// The YAML 1.1 types, as resolved by PyYAML: `yes`/`no`/`on`/`off`
+    /// booleans, `0b` binary and `0` octal integers, sexagesimal numbers
+    /// such as `1:30:00`, and `_` separators in numbers.
+    Yaml11,
+}
+
+impl Schema {
+    /// Resolve an untagged plain scalar.
+    ///
+    /// ```
+    /// use yaml_rust::schema::Schema;
+    /// use yaml_rust::Yaml;
+    ///
+    /// assert_eq!(Schema::Core.resolve("0o17"), Yaml::Integer(15));
+    /// assert_eq!(Schema::Yaml11.resolve("on"), Yaml::Boolean(true));
+    /// assert_eq!(Schema::Json.resolve("on"), Yaml::String("on".to_owned()));
+    /// ```
+    pub fn resolve(self, v: &str) -> Yaml {
+        let resolved = match self {
+            Schema::Failsafe => None,
+            Schema::Json => resolve_json(v),
+            Schema::Core => resolve_core(v),
+            Schema::Yaml11 => resolve_yaml11(v),
+        };
+        resolved.unwrap_or_else(|| Yaml::String(v.to_owned()))
+    }
+
+    /// Resolve a plain scalar tagged with `!!bool`, `!!int`, `!!float` or
+    /// `!!null`, returning `Yaml::BadValue` if it doesn't match the tag.
+    /// The failsafe schema has no such types, so it uses the core rules.
+    pub fn resolve_tagged(self, suffix: &str, v: &str) -> Yaml {
+        let schema = match self {
+            Schema::Failsafe => Schema::Core,
+            _ => self,
+        };
+        match (suffix, schema.resolve(v)) {
+            ("bool", y @ Yaml::Boolean(_))
+            | ("int", y @ Yaml::Integer(_))
+            | ("int", y @ Yaml::BigInteger(_))
+            | ("float", y @ Yaml::Real(_))
+            | ("null", y @ Yaml::Null) => y,
+            ("float", Yaml::Integer(i)) => Yaml::Real(i.to_string()),
+            ("float", Yaml::BigInteger(i)) => Yaml::Real(i.to_string()),
+            _ => Yaml::BadValue,
+        }
+    }
+}
+
+// split off a leading `-`, or `+` if allowed
+fn split_sign(v: &str, plus: bool) -> (&str, &str) {
+    if v.starts_with('-') || (plus && v.starts_with('+')) {
+        v.split_at(1)
+    } else {
+        ("", v)
+    }
+}
+
+fn is_digits(v: &str, radix: u32) -> bool {
+    !v.is_empty() && v.chars().all(|c| c.is_digit(radix))
+}
+
+// the number of leading ASCII digits in `v`
+fn digits_len(v: &str) -> usize {
+    v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len())
+}
+
+// whether `v` is an exponent such as `e10`, with an optional sign
+fn is_exponent(v: &str, sign_required: bool) -> bool {
+    if !v.starts_with('e') && !v.starts_with('E') {
+        return false;
+    }
+    let (sign, digits) = split_sign(&v[1..], true);
+    (!sign.is_empty() || !sign_required) && is_digits(digits, 10)
+}
+
+fn special_float(v: &str) -> bool {
+    match v {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" | "-.inf" | "-.Inf" | "-.INF"
+        | ".nan" | ".NaN" | ".NAN" => true,
+        _ => false,
+    }
+}
+
+fn parse_int(sign: &str, digits: &str, radix: u32) -> Option<Yaml> {
+    parse_integer(&format!("{}{}", sign, digits), radix)
+}
+
+fn resolve_json(v: &str) -> Option<Yaml> {
+    match v {
+        "null" => return Some(Yaml::Null),
+        "true" => return Some(Yaml::Boolean(true)),
+        "false" => return Some(Yaml::Boolean(false)),
+        _ => {}
+    }
+    let (_, number) = split_sign(v, false);
+    let int_len = digits_len(number);
+    if int_len == 0 || (int_len > 1 && number.starts_with('0')) {
+        return None;
+    }
+    let mut rest = &number[int_len..];
+    if rest.is_empty() {
+        if let Some(i) = parse_integer(v, 10) {
+            return Some(i);
+        }
+    }
+    if rest.starts_with('.') {
+        rest = &rest[1 + digits_len(&rest[1..])..];
+    }
+    if rest.is_empty() || is_exponent(rest, false) {
+        Some(Yaml::Real(v.to_owned()))
+    } else {
+        None
+    }
+}
+
+fn resolve_core(v: &str) -> Option<Yaml> {
+    match v {
+        "~" | "null" | "Null" | "NULL" | "" => return Some(Yaml::Null),
+        "true" | "True" | "TRUE" => return Some(Yaml::Boolean(true)),
+        "false" | "False" | "FALSE" => return Some(Yaml::Boolean(false)),
+        _ if special_float(v) => return Some(Yaml::Real(v.to_owned())),
+        _ => {}
+    }
+    if v.starts_with("0o") && is_digits(&v[2..], 8) {
+        return parse_int("", &v[2..], 8);
+    }
+    if v.starts_with("0x") && is_digits(&v[2..], 16) {
+        return parse_int("", &v[2..], 16);
+    }
+    let (sign, number) = split_sign(v, true);
+    if is_digits(number, 10) {
+        if let Some(i) = parse_int(sign, number, 10) {
+            return Some(i);
+        }
+    }
+    let int_len = digits_len(number);
+    let mut rest = &number[int_len..];
+    if rest.starts_with('.') {
+        let frac_len = digits_len(&rest[1..]);
+        if int_len == 0 && frac_len == 0 {
+            return None;
+        }
+        rest = &rest[1 + frac_len..];
+    } else if int_len == 0 {
+        return None;
+    }
+    if rest.is_empty() || is_exponent(rest, false) {
+        Some(Yaml::Real(v.to_owned()))
+    } else {
+        None
+    }
+}
+
+// the parts of a sexagesimal number such as `190:20:30`: the digits of the
+// first part, and the values of the others, which are below 60
+fn sexagesimal(v: &str) -> Option<(String, Vec<u8>)> {
+    let mut parts = v.split(':');
+    let first = parts.next()?.replace('_', "");
+    if !is_digits(&first, 10) {
+        return None;
+    }
+    let mut rest = Vec::new();
+    for part in parts {
+        if part.is_empty() || part.len() > 2 || !is_digits(part, 10) {
+            return None;
+        }
+        let n = part.parse::<u8>().ok()?;
+        if n >= 60 {
+            return None;
+        }
+        rest.push(n);
+    }
+    Some((first, rest))
+}
+
+fn resolve_yaml11(v: &str) -> Option<Yaml> {
+    match v {
+        "~" | "null" | "Null" | "NULL" | "" => return Some(Yaml::Null),
+        "true" | "True" | "TRUE" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => {
+            return Some(Yaml::Boolean(true))
+        }
+        "false" | "False" | "FALSE" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => {
+            return Some(Yaml::Boolean(false))
+        }
+        _ if special_float(v) => return Some(Yaml::Real(v.to_owned())),
+        _ => {}
+    }
+    let (sign, number) = split_sign(v, true);
+    if !number.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let digits = number.replace('_', "");
+
+    // integers
+    if number.starts_with("0b") {
+        return if is_digits(&digits[2..], 2) {
+            parse_int(sign, &digits[2..], 2)
+        } else {
+            None
+        };
+    }
+    if number.starts_with("0x") {
+        return if is_digits(&digits[2..], 16) {
+            parse_int(sign, &digits[2..], 16)
+        } else {
+            None
+        };
+    }
+    if number.starts_with('0') && number.len() > 1 && is_digits(&digits[1..], 8) {
+        return parse_int(sign, &digits[1..], 8);
+    }
+    if is_digits(&digits, 10) && (digits == "0" || !number.starts_with('0')) {
+        if let Some(i) = parse_int(sign, &digits, 10) {
+            return Some(i);
+        }
+    }
+    if number.contains(':') {
+        let (whole, frac) = match number.find('.') {
+            Some(pos) => (&number[..pos], &number[pos + 1..]),
+            None => (number, ""),
+        };
+        let frac = frac.replace('_', "");
+        if !frac.is_empty() && !is_digits(&frac, 10) {
+            return None;
+        }
+        let (first, rest) = sexagesimal(whole)?;
+        if number.contains('.') {
+            let mut value = first.parse::<f64>().ok()?;
+            for &n in &rest {
+                value = value * 60.0 + f64::from(n);
+            }
+            value += format!("0.{}", frac).parse::<f64>().ok()?;
+            return Some(Yaml::Real(format!("{}{:?}", sign, value)));
+        }
+        if number.starts_with('0') {
+            return None;
+        }
+        let mut value = first.parse::<i128>().ok();
+        for &n in &rest {
+            value = value
+                .and_then(|v| v.checked_mul(60))
+                .and_then(|v| v.checked_add(i128::from(n)));
+        }
+        return Some(match value {
+            Some(value) => parse_int(sign, &value.to_string(), 10)?,
+            None => Yaml::BadValue,
+        });
+    }
+
+    // floats, which need a `.` in YAML 1.1
+    let int_len = digits_len(&digits);
+    let rest = &digits[int_len..];
+    if !rest.starts_with('.') {
+        return None;
+    }
+    let frac_len = digits_len(&rest[1..]);
+    if int_len == 0 && frac_len == 0 {
+        return None;
+    }
+    let exp = &rest[1 + frac_len..];
+    if exp.is_empty() || is_exponent(exp, true) {
+        Some(Yaml::Real(format!("{}{}", sign, digits)))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn string(v: &str) -> Yaml {
+        Yaml::String(v.to_owned())
+    }
+
+    fn real(v: &str) -> Yaml {
+        Yaml::Real(v.to_owned())
+    }
+
+    #[test]
+    fn test_failsafe() {
+        for v in &["null", "true", "1", "1.5", "~"] {
+            assert_eq!(Schema::Failsafe.resolve(v), string(v));
+        }
+        assert_eq!(
+            Schema::Failsafe.resolve_tagged("int", "12"),
+            Yaml::Integer(12)
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let s = Schema::Json;
+        assert_eq!(s.resolve("null"), Yaml::Null);
+        assert_eq!(s.resolve("true"), Yaml::Boolean(true));
+        assert_eq!(s.resolve("-12"), Yaml::Integer(-12));
+        assert_eq!(s.resolve("0"), Yaml::Integer(0));
+        assert_eq!(s.resolve("1.5e-3"), real("1.5e-3"));
+        assert_eq!(s.resolve("-0.5"), real("-0.5"));
+        for v in &[
+            "Null", "~", "True", "+1", "012", "0x1F", ".5", "1e", ".inf", "yes",
+        ] {
+            assert_eq!(s.resolve(v), string(v));
+        }
+    }
+
+    #[test]
+    fn test_core() {
+        let s = Schema::Core;
+        for v in &["~", "null", "NULL", ""] {
+            assert_eq!(s.resolve(v), Yaml::Null);
+        }
+        assert_eq!(s.resolve("True"), Yaml::Boolean(true));
+        assert_eq!(s.resolve("FALSE"), Yaml::Boolean(false));
+        assert_eq!(s.resolve("+12"), Yaml::Integer(12));
+        assert_eq!(s.resolve("012"), Yaml::Integer(12));
+        assert_eq!(s.resolve("0o17"), Yaml::Integer(15));
+        assert_eq!(s.resolve("0x1F"), Yaml::Integer(31));
+        for v in &["1.", ".5", "-1.5E+3", "1e3", ".NaN", "-.inf"] {
+            assert_eq!(s.resolve(v), real(v));
+        }
+        for v in &[
+            "yes", "off", "0b11", "1_000", "1:30", ".", "0x", "1.5.2", "TrUe",
+        ] {
+            assert_eq!(s.resolve(v), string(v));
+        }
+        assert_eq!(s.resolve_tagged("float", "1"), real("1"));
+        assert_eq!(
+            s.resolve("-0x8000000000000000"),
+            string("-0x8000000000000000")
+        );
+        assert_eq!(s.resolve("0x8000000000000000"), Yaml::BigInteger(1 << 63));
+        assert_eq!(s.resolve(&"9".repeat(40)), Yaml::BadValue);
+        assert_eq!(
+            s.resolve_tagged("float", "9223372036854775808"),
+            real("9223372036854775808")
+        );
+        assert_eq!(s.resolve_tagged("bool", "1"), Yaml::BadValue);
+        assert_eq!(s.resolve_tagged("null", "~"), Yaml::Null);
+    }
+
+    #[test]
+    fn test_yaml11() {
+        let s = Schema::Yaml11;
+        for v in &["yes", "Yes", "ON", "true"] {
+            assert_eq!(s.resolve(v), Yaml::Boolean(true));
+        }
+        for v in &["no", "Off", "FALSE"] {
+            assert_eq!(s.resolve(v), Yaml::Boolean(false));
+        }
+        assert_eq!(s.resolve("~"), Yaml::Null);
+        assert_eq!(s.resolve("0b1010_0111"), Yaml::Integer(0b1010_0111));
+        assert_eq!(s.resolve("-0b11"), Yaml::Integer(-3));
+        assert_eq!(s.resolve("014"), Yaml::Integer(12));
+        assert_eq!(s.resolve("0x_0A_74_AE"), Yaml::Integer(0x0A_74_AE));
+        assert_eq!(s.resolve("+685_230"), Yaml::Integer(685_230));
+        assert_eq!(s.resolve("190:20:30"), Yaml::Integer(685_230));
+        assert_eq!(s.resolve("-1:30"), Yaml::Integer(-90));
+        // exact beyond 2^53, and beyond i64 like other integers
+        assert_eq!(
+            s.resolve("1:00:00:00:00:00:00:00:00:00:01"),
+            Yaml::Integer(604_661_760_000_000_001)
+        );
+        assert_eq!(
+            s.resolve("-1:00:00:00:00:00:00:00:00:00:00:00"),
+            Yaml::BigInteger(-36_279_705_600_000_000_000)
+        );
+        assert_eq!(s.resolve(&format!("{}:00", "9".repeat(38))), Yaml::BadValue);
+        assert_eq!(s.resolve("0"), Yaml::Integer(0));
+        assert_eq!(s.resolve("6.8523015e+5"), real("6.8523015e+5"));
+        assert_eq!(s.resolve("685_230.15"), real("685230.15"));
+        assert_eq!(s.resolve("190:20:30.15"), real("685230.15"));
+        assert_eq!(s.resolve("-1:30.5"), real("-90.5"));
+        assert_eq!(s.resolve(".5"), real(".5"));
+        assert_eq!(s.resolve(".inf"), real(".inf"));
+        for v in &[
+            "y", "n", "0o17", "1e3", "1:60", "09", "0b12", "_1", "1.5e3", "1:2:",
+        ] {
+            assert_eq!(s.resolve(v), string(v));
+        }
+        assert_eq!(s.resolve_tagged("bool", "off"), Yaml::Boolean(false));
+        assert_eq!(s.resolve_tagged("int", "1.5"), Yaml::BadValue);
+    }
+}
@@ -0,0 +1,493 @@
+//! Serialize Rust values as YAML with serde, when the `serde` feature is
+//! enabled.
+//!
+//! Values are built into a `Yaml` tree, which `YamlEmitter` then writes, so
+//! `to_yaml` can be used to edit a value before emitting it.
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     name: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let config = Config { name: "web".to_owned(), ports: vec![80, 443] };
+//! let s = yaml_rust::ser::to_string(&config).unwrap();
+//! assert_eq!(s, "---\nname: web\nports:\n  - 80\n  - 443");
+//! ```
+
+use crate::de::Error;
+use crate::emitter::YamlEmitter;
+use crate::number::Number;
+use crate::yaml::{Array, Hash, Yaml};
+use serde::ser::{self, Serialize};
+use std::convert::TryFrom;
+
+/// Serialize `value` as a YAML document.
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String, Error> {
+    let doc = to_yaml(value)?;
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&doc)
+        .map_err(<Error as ser::Error>::custom)?;
+    Ok(out)
+}
+
+/// Serialize `value` into a `Yaml` tree.
+///
+/// Integers become `Yaml::Integer`, or `Yaml::BigInteger` beyond the range
+/// of an `i64`. Byte slices become `Yaml::Binary`, `None` and `()` become
+/// `Yaml::Null`, and enum variants other than unit variants become a
+/// mapping with a single entry from the variant name to its value.
+pub fn to_yaml<T: ?Sized + Serialize>(value: &T) -> Result<Yaml, Error> {
+    value.serialize(Serializer)
+}
+
+fn integer(i: i128) -> Yaml {
+    match i64::try_from(i) {
+        Ok(i) => Yaml::Integer(i),
+        Err(_) => Yaml::BigInteger(i),
+    }
+}
+
+fn variant(name: &str, value: Yaml) -> Yaml {
+    let mut hash = Hash::new();
+    hash.insert(Yaml::String(name.to_owned()), value);
+    Yaml::Hash(hash)
+}
+
+/// A serializer that builds a `Yaml` tree, see `to_yaml`.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Yaml;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeHash;
+    type SerializeStruct = SerializeHash;
+    type SerializeStructVariant = SerializeHash;
+
+    fn serialize_bool(self, v: bool) -> Result<Yaml, Error> {
+        Ok(Yaml::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Yaml, Error> {
+        Ok(Yaml::Integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Yaml, Error> {
+        Ok(integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Yaml, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Yaml, Error> {
+        Ok(integer(i128::from(v)))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Yaml, Error> {
+        match i128::try_from(v) {
+            Ok(i) => Ok(integer(i)),
+            Err(_) => Err(ser::Error::custom(format!(
+                "integer {} is out of range for YAML",
+                v
+            ))),
+        }
+    }
+
+    /// Writes the shortest text that reads back as the same `f32`.
+    fn serialize_f32(self, v: f32) -> Result<Yaml, Error> {
+        if v.is_finite() {
+            Ok(Yaml::Real(format!("{:?}", v)))
+        } else {
+            self.serialize_f64(f64::from(v))
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Yaml, Error> {
+        Ok(Yaml::Real(Number::from(v).to_string()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Yaml, Error> {
+        Ok(Yaml::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Yaml, Error> {
+        Ok(Yaml::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Yaml, Error> {
+        Ok(Yaml::Binary(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Yaml, Error> {
+        Ok(Yaml::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Yaml, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Yaml, Error> {
+        Ok(Yaml::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Yaml, Error> {
+        Ok(Yaml::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Yaml, Error> {
+        Ok(Yaml::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Yaml, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Yaml, Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            variant: None,
+            array: Array::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            variant: Some(name),
+            array: Array::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeHash, Error> {
+        Ok(SerializeHash {
+            variant: None,
+            hash: Hash::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeHash, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<SerializeHash, Error> {
+        Ok(SerializeHash {
+            variant: Some(name),
+            hash: Hash::new(),
+            key: None,
+        })
+    }
+}
+
+/// Builds a `Yaml::Array` for `Serializer`.
+pub struct SerializeArray {
+    variant: Option<&'static str>,
+    array: Array,
+}
+
+impl SerializeArray {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.array.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Yaml, Error> {
+        let array = Yaml::Array(self.array);
+        Ok(match self.variant {
+            Some(name) => variant(name, array),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+/// Builds a `Yaml::Hash` for `Serializer`.
+pub struct SerializeHash {
+    variant: Option<&'static str>,
+    hash: Hash,
+    // the key of the entry being serialized, between `serialize_key` and
+    // `serialize_value`
+    key: Option<Yaml>,
+}
+
+impl SerializeHash {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer)?;
+        self.hash.insert(Yaml::String(key.to_owned()), value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Yaml, Error> {
+        let hash = Yaml::Hash(self.hash);
+        Ok(match self.variant {
+            Some(name) => variant(name, hash),
+            None => hash,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeHash {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        self.hash.insert(key, value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeHash {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeHash {
+    type Ok = Yaml;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Yaml, Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::de::from_str;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Doc {
+        name: String,
+        version: String,
+        count: u64,
+        ratio: f32,
+        missing: Option<i8>,
+        shapes: Vec<Shape>,
+        labels: BTreeMap<u32, bool>,
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        assert_eq!(
+            to_yaml(&u64::MAX).unwrap(),
+            Yaml::BigInteger(i128::from(u64::MAX))
+        );
+        assert_eq!(to_yaml(&-1i8).unwrap(), Yaml::Integer(-1));
+        assert!(to_yaml(&u128::MAX).is_err());
+        assert_eq!(to_yaml(&0.1f32).unwrap(), Yaml::Real("0.1".to_owned()));
+        assert_eq!(
+            to_yaml(&f64::NEG_INFINITY).unwrap(),
+            Yaml::Real("-.inf".to_owned())
+        );
+        assert_eq!(
+            to_yaml(&Shape::Empty).unwrap(),
+            Yaml::String("Empty".to_owned())
+        );
+        assert_eq!(
+            to_yaml(&Shape::Circle(1.0)).unwrap(),
+            variant("Circle", Yaml::Real("1.0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut labels = BTreeMap::new();
+        labels.insert(1, true);
+        labels.insert(20, false);
+        let doc = Doc {
+            name: "a: b".to_owned(),
+            version: "1.10".to_owned(),
+            count: u64::MAX,
+            ratio: 0.25,
+            missing: None,
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(-1.5),
+                Shape::Point(1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            labels,
+        };
+        let s = to_string(&doc).unwrap();
+        assert_eq!(from_str::<Doc>(&s).unwrap(), doc);
+        assert!(s.contains("version: \"1.10\""));
+
+        assert_eq!(
+            to_string(&Bytes(&[0, 1, 2])).unwrap(),
+            "---\n!!binary |\n  AAEC"
+        );
+    }
+
+    // serializes as bytes rather than a sequence of integers
+    struct Bytes<'a>(&'a [u8]);
+
+    impl<'a> Serialize for Bytes<'a> {
+        fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(self.0)
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! The YAML [timestamp](https://yaml.org/type/timestamp.html) type.
+
+use std::fmt;
+
+/// A date, or a date and time, parsed from a YAML timestamp.
+///
+/// `Yaml::Timestamp` keeps the text as written, like `Yaml::Real`, and
+/// `Yaml::as_timestamp` parses it into this type.
+///
+/// ```
+/// use yaml_rust::timestamp::Timestamp;
+///
+/// let t = Timestamp::parse("2001-12-14t21:59:43.10-05:00").unwrap();
+/// assert_eq!((t.year, t.month, t.day), (2001, 12, 14));
+/// let time = t.time.unwrap();
+/// assert_eq!(time.nanosecond, 100_000_000);
+/// assert_eq!(time.offset_minutes, Some(-300));
+/// assert_eq!(t.to_string(), "2001-12-14T21:59:43.1-05:00");
+/// ```
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    /// The time of day, or `None` for a date alone.
+    pub time: Option<Time>,
+}
+
+/// The time of day of a `Timestamp`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60, to allow for leap seconds.
+    pub second: u8,
+    pub nanosecond: u32,
+    /// The offset from UTC in minutes, or `None` if no time zone was given,
+    /// which YAML reads as UTC.
+    pub offset_minutes: Option<i32>,
+}
+
+// reads the fields of a timestamp from left to right
+struct Reader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    // between `min` and `max` digits
+    fn digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        let len = self
+            .rest
+            .bytes()
+            .take(max)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if len < min {
+            return None;
+        }
+        let (digits, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        digits.parse().ok()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest.starts_with(c) {
+            self.rest = &self.rest[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_spaces(&mut self) -> bool {
+        let trimmed = self.rest.trim_start_matches(&[' ', '\t'][..]);
+        let found = trimmed.len() < self.rest.len();
+        self.rest = trimmed;
+        found
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let leap_year = match (year % 4, year % 100, year % 400) {
+        (0, 0, 0) => true,
+        (0, 0, _) => false,
+        (r, _, _) => r == 0,
+    };
+    match month {
+        2 if leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Timestamp {
+    /// Parse a timestamp in any of the forms the YAML timestamp regex
+    /// accepts, e.g. `2002-12-14`, `2001-12-14 21:59:43.10 -5` or
+    /// `2001-12-15T02:59:43.1Z`. Returns `None` if `v` is not a valid
+    /// timestamp.
+    pub fn parse(v: &str) -> Option<Timestamp> {
+        let mut r = Reader { rest: v };
+        let year = r.digits(4, 4)?;
+        if !r.eat('-') {
+            return None;
+        }
+        // a date alone needs two digit months and days
+        let date_only = v.len() == 10;
+        let (min, max) = if date_only { (2, 2) } else { (1, 2) };
+        let month = r.digits(min, max)?;
+        if !r.eat('-') {
+            return None;
+        }
+        let day = r.digits(min, max)?;
+        if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        let mut ts = Timestamp {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            time: None,
+        };
+        if r.rest.is_empty() {
+            return if date_only { Some(ts) } else { None };
+        }
+
+        if !r.eat('T') && !r.eat('t') && !r.eat_spaces() {
+            return None;
+        }
+        let hour = r.digits(1, 2)?;
+        if !r.eat(':') {
+            return None;
+        }
+        let minute = r.digits(2, 2)?;
+        if !r.eat(':') {
+            return None;
+        }
+        let second = r.digits(2, 2)?;
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        let mut nanosecond = 0;
+        if r.eat('.') {
+            let len = r.rest.bytes().take_while(u8::is_ascii_digit).count();
+            let (fraction, rest) = r.rest.split_at(len);
+            r.rest = rest;
+            // digits beyond nanoseconds are dropped
+            for (i, c) in fraction.bytes().take(9).enumerate() {
+                nanosecond += u32::from(c - b'0') * 10u32.pow(8 - i as u32);
+            }
+        }
+
+        r.eat_spaces();
+        let offset_minutes = if r.rest.is_empty() {
+            None
+        } else if r.eat('Z') {
+            Some(0)
+        } else {
+            let sign = if r.eat('+') {
+                1
+            } else if r.eat('-') {
+                -1
+            } else {
+                return None;
+            };
+            let hours = r.digits(1, 2)?;
+            let minutes = if r.eat(':') { r.digits(2, 2)? } else { 0 };
+            if minutes > 59 {
+                return None;
+            }
+            Some(sign * (hours * 60 + minutes) as i32)
+        };
+        if !r.rest.is_empty() {
+            return None;
+        }
+
+        ts.time = Some(Time {
+            hour: hour as u8,
+            minute: minute as u8,
+            second: second as u8,
+            nanosecond,
+            offset_minutes,
+        });
+        Some(ts)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Formats in the canonical form, e.g. `2001-12-14T21:59:43.1-05:00`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+        let time = match self.time {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+        write!(f, "T{:02}:{:02}:{:02}", time.hour, time.minute, time.second)?;
+        if time.nanosecond > 0 {
+            let fraction = format!("{:09}", time.nanosecond);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        match time.offset_minutes {
+            None | Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let canonical = |v: &str| Timestamp::parse(v).map(|t| t.to_string());
+        assert_eq!(canonical("2002-12-14").unwrap(), "2002-12-14");
+        assert_eq!(
+            canonical("2001-12-14t21:59:43.10-05:00").unwrap(),
+            "2001-12-14T21:59:43.1-05:00"
+        );
+        assert_eq!(
+            canonical("2001-12-14 21:59:43.10 -5").unwrap(),
+            "2001-12-14T21:59:43.1-05:00"
+        );
+        assert_eq!(
+            canonical("2001-12-15T02:59:43.1Z").unwrap(),
+            "2001-12-15T02:59:43.1Z"
+        );
+        assert_eq!(
+            canonical("2001-12-15 2:59:43.10").unwrap(),
+            "2001-12-15T02:59:43.1Z"
+        );
+        assert_eq!(
+            canonical("2001-1-5\t\t02:59:43 +05:30").unwrap(),
+            "2001-01-05T02:59:43+05:30"
+        );
+        assert_eq!(
+            canonical("2000-02-29T00:00:00.0123456789Z").unwrap(),
+            "2000-02-29T00:00:00.012345678Z"
+        );
+
+        for bad in &[
+            "2002-1-14",
+            "2002-12-14 ",
+            "2002-13-01",
+            "2001-02-29",
+            "02-12-14",
+            "2002-12-14T",
+            "2002-12-14T25:00:00",
+            "2002-12-14T10:00",
+            "2002-12-14T10:00:00 +5:3",
+            "2002-12-14T10:00:00X",
+            "2002-12-14x10:00:00",
+        ] {
+            assert_eq!(Timestamp::parse(bad), None, "{}", bad);
+        }
+    }
+}
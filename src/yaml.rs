@@ -1,12 +1,27 @@
 use linked_hash_map::LinkedHashMap;
+use crate::base64;
+use crate::encoding;
+use crate::number::Number;
 use crate::parser::*;
-use crate::scanner::{Marker, ScanError, TScalarStyle, TokenType};
-use std::collections::BTreeMap;
+use crate::scanner::{Limit, Marker, ScanError, TEncoding, TScalarStyle, TokenType};
+use crate::schema::Schema;
+use crate::timestamp::Timestamp;
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
 use std::f64;
+use std::fmt;
+use std::hash;
 use std::i64;
+use std::io;
 use std::mem;
-use std::ops::Index;
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+use std::str;
 use std::string;
+use std::sync::Arc;
 use std::vec;
 
 /// A YAML node is stored as this `Yaml` enumeration, which provides an easy way to
@@ -32,6 +47,13 @@ pub enum Yaml {
     Real(string::String),
     /// YAML int is stored as i64.
     Integer(i64),
+    /// An integer outside the range of `i64`. Integers that fit in an `i64`
+    /// are always stored as `Integer`.
+    BigInteger(i128),
+    /// An integer or a float that keeps the text it was written as, loaded
+    /// instead of `Integer`, `BigInteger` and `Real` when
+    /// `LoaderOptions::numbers` is set.
+    Number(Number),
     /// YAML scalar.
     String(string::String),
     /// YAML bool, e.g. `true` or `false`.
@@ -42,6 +64,17 @@ pub enum Yaml {
     ///
     /// Insertion order will match the order of insertion into the map.
     Hash(self::Hash),
+    /// Bytes decoded from a `!!binary` scalar.
+    Binary(Vec<u8>),
+    /// A date or date and time, stored as written and parsed on demand.
+    /// Loaded from `!!timestamp` scalars, and from untagged plain scalars
+    /// when `LoaderOptions::timestamps` is set.
+    Timestamp(string::String),
+    /// A node with an application-specific tag, e.g. `!secret`, kept as
+    /// written. Tagged scalars hold a `Yaml::String`. This also holds the
+    /// `!!set`, `!!omap` and `!!pairs` collection types; an `!!omap` holds
+    /// a `Yaml::Hash` with its pairs in order.
+    Tagged(string::String, Box<Yaml>),
     /// Alias, not fully supported yet.
     Alias(usize),
     /// YAML null, e.g. `null` or `~`.
@@ -57,27 +90,439 @@ pub type Hash = LinkedHashMap<Yaml, Yaml>;
 
 // parse f64 as Core schema
 // See: https://github.com/chyh1990/yaml-rust/issues/51
-fn parse_f64(v: &str) -> Option<f64> {
+pub(crate) fn parse_f64(v: &str) -> Option<f64> {
     match v {
         ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
         "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
-        ".nan" | "NaN" | ".NAN" => Some(f64::NAN),
+        ".nan" | ".NaN" | "NaN" | ".NAN" => Some(f64::NAN),
         _ => v.parse::<f64>().ok(),
     }
 }
 
-pub struct YamlLoader {
-    docs: Vec<Yaml>,
+// Resolve a plain scalar with a `!!` tag, returning `Yaml::BadValue` if it
+// doesn't match the tag. Tags without a type of their own give a string.
+pub(crate) fn resolve_core_tag(suffix: &str, v: String) -> Yaml {
+    match suffix {
+        "bool" => {
+            // "true" or "false"
+            match v.parse::<bool>() {
+                Err(_) => Yaml::BadValue,
+                Ok(v) => Yaml::Boolean(v),
+            }
+        }
+        "int" => parse_integer(&v, 10).unwrap_or(Yaml::BadValue),
+        "float" => match parse_f64(&v) {
+            Some(_) => Yaml::Real(v),
+            None => Yaml::BadValue,
+        },
+        "null" => match v.as_ref() {
+            "~" | "null" => Yaml::Null,
+            _ => Yaml::BadValue,
+        },
+        _ => Yaml::String(v),
+    }
+}
+
+// Parse an integer with an optional sign. Integers that don't fit in an i64
+// become `Yaml::BigInteger`, and those that don't fit in an i128 are
+// reported as `Yaml::BadValue` rather than read as floats. Returns `None` if
+// `v` is not an integer.
+pub(crate) fn parse_integer(v: &str, radix: u32) -> Option<Yaml> {
+    match i128::from_str_radix(v, radix) {
+        Ok(i) => Some(match i64::try_from(i) {
+            Ok(i) => Yaml::Integer(i),
+            Err(_) => Yaml::BigInteger(i),
+        }),
+        Err(_) => {
+            // a well-formed integer that doesn't fit
+            let digits = if v.starts_with('+') || v.starts_with('-') {
+                &v[1..]
+            } else {
+                v
+            };
+            if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) {
+                Some(Yaml::BadValue)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// A node type that `YamlLoader` can build a document tree of.
+///
+/// This is implemented by `Yaml`, and by `MarkedYaml` for trees that remember
+/// where each node was found in the source.
+pub trait LoadableYamlNode: Clone + hash::Hash + Eq {
+    /// Build a node from a scalar, or from an empty `Yaml::Array` or
+    /// `Yaml::Hash` that the loader then fills through `array_mut` and
+    /// `hash_mut`.
+    fn from_bare_yaml(yaml: Yaml, start: Marker, end: Marker) -> Self;
+
+    fn array_mut(&mut self) -> Option<&mut Vec<Self>>;
+
+    fn hash_mut(&mut self) -> Option<&mut LinkedHashMap<Self, Self>>;
+
+    fn array_ref(&self) -> Option<&Vec<Self>>;
+
+    fn hash_ref(&self) -> Option<&LinkedHashMap<Self, Self>>;
+
+    fn is_null(&self) -> bool;
+
+    /// Wrap a complete node in an application-specific tag.
+    fn into_tagged(self, tag: String) -> Self;
+
+    /// Convert a complete node into a plain `Yaml` tree.
+    fn into_bare_yaml(self) -> Yaml;
+
+    /// Whether `into_bare_yaml` copies the nodes that aliases share, so the
+    /// loader counts the copy against `LoaderOptions::max_alias_expansion`.
+    fn expands_aliases() -> bool {
+        false
+    }
+
+    /// Called with the marker of the closing event once a collection is
+    /// complete.
+    fn set_end(&mut self, _end: Marker) {}
+}
+
+impl LoadableYamlNode for Yaml {
+    fn from_bare_yaml(yaml: Yaml, _: Marker, _: Marker) -> Self {
+        yaml
+    }
+
+    fn array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match *self {
+            Yaml::Array(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn hash_mut(&mut self) -> Option<&mut LinkedHashMap<Self, Self>> {
+        match *self {
+            Yaml::Hash(ref mut h) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn array_ref(&self) -> Option<&Vec<Self>> {
+        self.as_vec()
+    }
+
+    fn hash_ref(&self) -> Option<&LinkedHashMap<Self, Self>> {
+        self.as_hash()
+    }
+
+    fn is_null(&self) -> bool {
+        Yaml::is_null(self)
+    }
+
+    fn into_tagged(self, tag: String) -> Self {
+        Yaml::Tagged(tag, Box::new(self))
+    }
+
+    fn into_bare_yaml(self) -> Yaml {
+        self
+    }
+}
+
+// whether `tag` is the `!!` tag with this suffix
+fn is_tag(tag: &Option<TokenType>, suffix: &str) -> bool {
+    match *tag {
+        Some(TokenType::Tag(ref h, ref s)) => h == "!!" && s == suffix,
+        _ => false,
+    }
+}
+
+// What is wrong with a scalar that resolved to `Yaml::BadValue`: either it
+// doesn't match its tag, or it's an integer too large for an i128.
+pub(crate) fn bad_value_info(tag: &Option<TokenType>) -> String {
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) => {
+            format!("while loading {}{}, found an invalid value", handle, suffix)
+        }
+        _ => "while loading an integer, found a value out of range".to_owned(),
+    }
+}
+
+// The `!!` tags the loader resolves to a `Yaml` type, for scalars and for
+// collections.
+const SCALAR_TAGS: &[&str] = &["bool", "int", "float", "null", "str", "binary", "timestamp"];
+const COLLECTION_TAGS: &[&str] = &["seq", "map"];
+
+// The tag as written, unless the loader resolves it to a `Yaml` type.
+fn custom_tag(tag: &Option<TokenType>, resolved: &[&str]) -> Option<String> {
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) => {
+            if (handle == "!!" && resolved.contains(&suffix.as_str()))
+                || (handle.is_empty() && suffix == "!")
+            {
+                None
+            } else if handle.is_empty() {
+                Some(format!("!<{}>", suffix))
+            } else {
+                Some(format!("{}{}", handle, suffix))
+            }
+        }
+        _ => None,
+    }
+}
+
+// Check the structure of the `!!set`, `!!omap` and `!!pairs` collection
+// types. An `!!omap` is turned into a mapping that keeps the order of its
+// pairs, the others are kept as written.
+fn load_collection_type<Node: LoadableYamlNode>(
+    tag: &str,
+    node: Node,
+    mark: Marker,
+    end: Marker,
+) -> Result<Node, ScanError> {
+    match tag {
+        "!!set" => {
+            let all_null = node
+                .hash_ref()
+                .map_or(false, |h| h.values().all(Node::is_null));
+            if !all_null {
+                return Err(ScanError::new(
+                    mark,
+                    "while loading !!set, expected a mapping with null values",
+                ));
+            }
+            Ok(node)
+        }
+        "!!omap" | "!!pairs" => {
+            let expected = format!(
+                "while loading {}, expected a sequence of single-pair mappings",
+                tag
+            );
+            let pairs = match node.array_ref() {
+                Some(v) => v,
+                None => return Err(ScanError::new(mark, &expected)),
+            };
+            let mut pair_list = Vec::with_capacity(pairs.len());
+            for pair in pairs {
+                match pair.hash_ref() {
+                    Some(h) if h.len() == 1 => pair_list.push(h.iter().next().unwrap()),
+                    _ => return Err(ScanError::new(mark, &expected)),
+                }
+            }
+            if tag == "!!pairs" {
+                return Ok(node);
+            }
+
+            let mut omap = Node::from_bare_yaml(Yaml::Hash(Hash::new()), mark, end);
+            let h = omap.hash_mut().unwrap();
+            for (k, v) in pair_list {
+                if h.insert(k.clone(), v.clone()).is_some() {
+                    return Err(ScanError::new(
+                        mark,
+                        "while loading !!omap, found a duplicate key",
+                    ));
+                }
+            }
+            Ok(omap)
+        }
+        _ => Ok(node),
+    }
+}
+
+/// What the loader does when a mapping contains the same key twice.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum DuplicateKeys {
+    /// Fail with an error that reports both occurrences of the key.
+    Error,
+    /// Keep the value of the first occurrence.
+    FirstWins,
+    /// Keep the value of the last occurrence, moving the entry to the end.
+    LastWins,
+    /// Collect the values of all occurrences into an array, in order.
+    KeepAll,
+}
+
+impl Default for DuplicateKeys {
+    fn default() -> DuplicateKeys {
+        DuplicateKeys::LastWins
+    }
+}
+
+/// A function that builds the value of a node with an application-specific
+/// tag. It receives the node as loaded, a `Yaml::String` for scalars, and
+/// the `Marker` where the node starts.
+///
+/// A constructor always receives a plain `Yaml` tree, so with
+/// `YamlLoader::<SharedYaml>` every alias within a tagged collection is
+/// expanded, and the expanded nodes count against
+/// `LoaderOptions::max_alias_expansion`.
+pub type TagConstructor = dyn Fn(Yaml, Marker) -> Result<Yaml, ScanError> + Send + Sync;
+
+/// Constructors for application-specific tags, by tag as written.
+///
+/// ```
+/// use yaml_rust::yaml::LoaderOptions;
+/// use yaml_rust::{ScanError, Yaml, YamlLoader};
+///
+/// let mut options = LoaderOptions::default();
+/// options.constructors.insert("!double", |v, mark| match v.as_str() {
+///     Some(s) => Ok(Yaml::String(s.repeat(2))),
+///     None => Err(ScanError::new(mark, "!double expects a scalar")),
+/// });
+/// let docs = YamlLoader::<Yaml>::load_with_options("a: !double ab", &options).unwrap();
+/// assert_eq!(docs[0]["a"].as_str(), Some("abab"));
+/// ```
+#[derive(Clone, Default)]
+pub struct TagConstructors {
+    map: HashMap<String, Arc<TagConstructor>>,
+}
+
+impl TagConstructors {
+    /// Register the constructor for `tag`, e.g. `!env`, replacing any
+    /// previous one.
+    pub fn insert<F>(&mut self, tag: &str, constructor: F)
+    where
+        F: Fn(Yaml, Marker) -> Result<Yaml, ScanError> + Send + Sync + 'static,
+    {
+        self.map.insert(tag.to_owned(), Arc::new(constructor));
+    }
+
+    pub fn remove(&mut self, tag: &str) -> bool {
+        self.map.remove(tag).is_some()
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&TagConstructor> {
+        self.map.get(tag).map(|f| &**f)
+    }
+}
+
+impl fmt::Debug for TagConstructors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.map.keys()).finish()
+    }
+}
+
+/// Options for `YamlLoader::load_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct LoaderOptions {
+    /// Limits enforced by the parser.
+    pub parser: ParserOptions,
+    /// Maximum number of nodes added to the documents by resolving aliases.
+    /// Each alias counts as all the nodes of its anchor, even when the node
+    /// type shares them rather than copying them.
+    pub max_alias_expansion: Option<usize>,
+    /// Resolve merge keys (`<<`) as described in the YAML 1.1
+    /// [merge type](https://yaml.org/type/merge.html), instead of loading
+    /// them as plain `"<<"` keys.
+    pub merge_keys: bool,
+    /// How to handle duplicate keys in a mapping.
+    pub duplicate_keys: DuplicateKeys,
+    /// The schema used to resolve plain scalars. `None` resolves them with
+    /// `Yaml::from_str`.
+    pub schema: Option<Schema>,
+    /// Constructors for nodes with application-specific tags. Nodes whose
+    /// tag has no constructor are loaded as `Yaml::Tagged`.
+    pub constructors: TagConstructors,
+    /// Load untagged plain scalars that match the YAML timestamp regex,
+    /// e.g. `2001-12-14`, as `Yaml::Timestamp` instead of strings.
+    pub timestamps: bool,
+    /// Load plain scalars that resolve to integers or floats as
+    /// `Yaml::Number`, which keeps their radix and the text they were
+    /// written as.
+    pub numbers: bool,
+    /// Fail with an error instead of loading `Yaml::BadValue` for scalars
+    /// that can't be resolved: explicitly tagged scalars that don't match
+    /// their tag, such as `!!int abc`, and integers too large to represent.
+    pub strict: bool,
+}
+
+// state of a mapping that is being loaded
+struct MappingState<Node> {
+    // the key whose value comes next, and where it starts
+    key: Option<Node>,
+    key_mark: Marker,
+    // where each key starts; only kept when needed to handle duplicates
+    key_marks: HashMap<Node, Marker>,
+    // keys whose values have been collected into an array
+    repeated: HashSet<Node>,
+    // whether `key` is a merge key
+    merge: bool,
+    // values of the merge keys, with the number of entries before them
+    merges: Vec<(usize, Node, Marker)>,
+}
+
+// Merge the values of the merge keys into the mapping `node`. Explicit keys
+// take precedence over merged ones, and earlier merged mappings over later
+// ones. Merged entries are placed where their merge key was.
+fn apply_merges<Node: LoadableYamlNode>(
+    node: &mut Node,
+    merges: Vec<(usize, Node, Marker)>,
+) -> Result<(), ScanError> {
+    let explicit = mem::replace(node.hash_mut().unwrap(), LinkedHashMap::new());
+    let mut merged = LinkedHashMap::new();
+    for (pos, value, mark) in merges {
+        let sources = match value.array_ref() {
+            Some(v) => v.iter().collect(),
+            None => vec![&value],
+        };
+        for source in sources {
+            let h = source.hash_ref().ok_or_else(|| {
+                ScanError::new(
+                    mark,
+                    "while loading a merge key, expected a mapping or a sequence of mappings",
+                )
+            })?;
+            for (k, v) in h {
+                if !explicit.contains_key(k) && !merged.contains_key(k) {
+                    merged.insert(k.clone(), (pos, v.clone()));
+                }
+            }
+        }
+    }
+
+    let h = node.hash_mut().unwrap();
+    let mut merged = merged.into_iter().peekable();
+    for (i, (k, v)) in explicit.into_iter().enumerate() {
+        while let Some(&(_, (pos, _))) = merged.peek() {
+            if pos > i {
+                break;
+            }
+            let (mk, (_, mv)) = merged.next().unwrap();
+            h.insert(mk, mv);
+        }
+        h.insert(k, v);
+    }
+    for (mk, (_, mv)) in merged {
+        h.insert(mk, mv);
+    }
+    Ok(())
+}
+
+pub struct YamlLoader<Node: LoadableYamlNode = Yaml> {
+    docs: Vec<Node>,
     // states
     // (current node, anchor_id) tuple
-    doc_stack: Vec<(Yaml, usize)>,
-    key_stack: Vec<Yaml>,
-    anchor_map: BTreeMap<usize, Yaml>,
+    doc_stack: Vec<(Node, usize)>,
+    map_stack: Vec<MappingState<Node>>,
+    anchor_map: BTreeMap<usize, Node>,
+    options: LoaderOptions,
+    // the first error found; later events are ignored
+    error: Option<ScanError>,
+    // number of nodes loaded so far, and the count, start and custom tag of
+    // each open collection
+    node_count: usize,
+    starts: Vec<(usize, Marker, Option<String>)>,
+    anchor_sizes: BTreeMap<usize, usize>,
+    expanded: usize,
 }
 
-impl MarkedEventReceiver for YamlLoader {
-    fn on_event(&mut self, ev: Event, _: Marker) {
+impl<Node: LoadableYamlNode> MarkedEventReceiver for YamlLoader<Node> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.on_spanned_event(ev, mark, mark)
+    }
+
+    fn on_spanned_event(&mut self, ev: Event, mark: Marker, end: Marker) {
         // println!("EV {:?}", ev);
+        if self.error.is_some() {
+            return;
+        }
         match ev {
             Event::DocumentStart => {
                 // do nothing
@@ -85,71 +530,171 @@ impl MarkedEventReceiver for YamlLoader {
             Event::DocumentEnd => {
                 match self.doc_stack.len() {
                     // empty document
-                    0 => self.docs.push(Yaml::BadValue),
+                    0 => self
+                        .docs
+                        .push(Node::from_bare_yaml(Yaml::BadValue, mark, end)),
                     1 => self.docs.push(self.doc_stack.pop().unwrap().0),
                     _ => unreachable!(),
                 }
+                // aliases can't refer to the anchors of other documents
+                self.anchor_map.clear();
+                self.anchor_sizes.clear();
             }
-            Event::SequenceStart(aid) => {
-                self.doc_stack.push((Yaml::Array(Vec::new()), aid));
+            Event::SequenceStart(aid, tag) => {
+                let node = Node::from_bare_yaml(Yaml::Array(Vec::new()), mark, end);
+                self.doc_stack.push((node, aid));
+                self.starts
+                    .push((self.node_count, mark, custom_tag(&tag, COLLECTION_TAGS)));
+                self.node_count = self.node_count.saturating_add(1);
             }
             Event::SequenceEnd => {
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                let (mut node, aid) = self.doc_stack.pop().unwrap();
+                node.set_end(end);
+                let (start, tag, size) = self.end_collection(aid);
+                if let Some(tag) = tag {
+                    node = match self.construct(tag, node, size, start, end) {
+                        Ok(node) => node,
+                        Err(e) => {
+                            self.error = Some(e);
+                            return;
+                        }
+                    };
+                }
+                self.insert_new_node((node, aid), start);
             }
-            Event::MappingStart(aid) => {
-                self.doc_stack.push((Yaml::Hash(Hash::new()), aid));
-                self.key_stack.push(Yaml::BadValue);
+            Event::MappingStart(aid, tag) => {
+                let node = Node::from_bare_yaml(Yaml::Hash(Hash::new()), mark, end);
+                self.doc_stack.push((node, aid));
+                self.starts
+                    .push((self.node_count, mark, custom_tag(&tag, COLLECTION_TAGS)));
+                self.map_stack.push(MappingState {
+                    key: None,
+                    key_mark: mark,
+                    key_marks: HashMap::new(),
+                    repeated: HashSet::new(),
+                    merge: false,
+                    merges: Vec::new(),
+                });
+                self.node_count = self.node_count.saturating_add(1);
             }
             Event::MappingEnd => {
-                self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                let state = self.map_stack.pop().unwrap();
+                let (mut node, aid) = self.doc_stack.pop().unwrap();
+                node.set_end(end);
+                if !state.merges.is_empty() {
+                    if let Err(e) = apply_merges(&mut node, state.merges) {
+                        self.error = Some(e);
+                        return;
+                    }
+                }
+                let (start, tag, size) = self.end_collection(aid);
+                if let Some(tag) = tag {
+                    node = match self.construct(tag, node, size, start, end) {
+                        Ok(node) => node,
+                        Err(e) => {
+                            self.error = Some(e);
+                            return;
+                        }
+                    };
+                }
+                self.insert_new_node((node, aid), start);
             }
             Event::Scalar(v, style, aid, tag) => {
-                let node = if style != TScalarStyle::Plain {
-                    Yaml::String(v)
+                let custom_tag = custom_tag(&tag, SCALAR_TAGS);
+                let literal = if self.options.numbers && style == TScalarStyle::Plain {
+                    Some(v.clone())
+                } else {
+                    None
+                };
+                let merge_key = self.options.merge_keys
+                    && style == TScalarStyle::Plain
+                    && v == "<<"
+                    && (tag.is_none() || is_tag(&tag, "merge"))
+                    && self.at_mapping_key();
+                let node = if is_tag(&tag, "binary") {
+                    match base64::decode(&v) {
+                        Some(bytes) => Yaml::Binary(bytes),
+                        None => Yaml::BadValue,
+                    }
+                } else if is_tag(&tag, "timestamp") {
+                    match Timestamp::parse(&v) {
+                        Some(_) => Yaml::Timestamp(v),
+                        None => Yaml::BadValue,
+                    }
                 } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
                     // XXX tag:yaml.org,2002:
+                    // a `!!` tag applies whatever the quoting style
                     if handle == "!!" {
                         match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match v.parse::<bool>() {
-                                    Err(_) => Yaml::BadValue,
-                                    Ok(v) => Yaml::Boolean(v),
-                                }
+                            "bool" | "int" | "float" | "null" if self.options.schema.is_some() => {
+                                self.options.schema.unwrap().resolve_tagged(suffix, &v)
                             }
-                            "int" => match v.parse::<i64>() {
-                                Err(_) => Yaml::BadValue,
-                                Ok(v) => Yaml::Integer(v),
-                            },
-                            "float" => match parse_f64(&v) {
-                                Some(_) => Yaml::Real(v),
-                                None => Yaml::BadValue,
-                            },
-                            "null" => match v.as_ref() {
-                                "~" | "null" => Yaml::Null,
-                                _ => Yaml::BadValue,
-                            },
-                            _ => Yaml::String(v),
+                            _ => resolve_core_tag(suffix, v),
                         }
                     } else {
                         Yaml::String(v)
                     }
+                } else if style != TScalarStyle::Plain {
+                    Yaml::String(v)
                 } else {
                     // Datatype is not specified, or unrecognized
-                    Yaml::from_str(&v)
+                    let node = match self.options.schema {
+                        Some(schema) => schema.resolve(&v),
+                        None => Yaml::from_str(&v),
+                    };
+                    match node {
+                        Yaml::String(v)
+                            if self.options.timestamps && Timestamp::parse(&v).is_some() =>
+                        {
+                            Yaml::Timestamp(v)
+                        }
+                        node => node,
+                    }
+                };
+                if self.options.strict && node.is_badvalue() {
+                    self.error = Some(ScanError::new(mark, &bad_value_info(&tag)));
+                    return;
+                }
+                let node = match literal.and_then(|v| Number::from_resolved(v, &node)) {
+                    Some(n) => Yaml::Number(n),
+                    None => node,
                 };
 
-                self.insert_new_node((node, aid));
+                let mut node = Node::from_bare_yaml(node, mark, end);
+                if let Some(tag) = custom_tag {
+                    node = match self.construct(tag, node, 1, mark, end) {
+                        Ok(node) => node,
+                        Err(e) => {
+                            self.error = Some(e);
+                            return;
+                        }
+                    };
+                }
+
+                self.node_count = self.node_count.saturating_add(1);
+                if aid > 0 {
+                    self.anchor_sizes.insert(aid, 1);
+                }
+                self.insert_new_node((node, aid), mark);
+                if merge_key {
+                    self.map_stack.last_mut().unwrap().merge = true;
+                }
             }
             Event::Alias(id) => {
                 let n = match self.anchor_map.get(&id) {
                     Some(v) => v.clone(),
-                    None => Yaml::BadValue,
+                    None => Node::from_bare_yaml(Yaml::BadValue, mark, end),
                 };
-                self.insert_new_node((n, 0));
+                let size = self.anchor_sizes.get(&id).cloned().unwrap_or(1);
+                self.node_count = self.node_count.saturating_add(size);
+                self.expanded = self.expanded.saturating_add(size);
+                if let Some(max) = self.options.max_alias_expansion {
+                    if self.expanded > max {
+                        self.error = Some(ScanError::limit_exceeded(mark, Limit::AliasExpansion));
+                        return;
+                    }
+                }
+                self.insert_new_node((n, 0), mark);
             }
             _ => { /* ignore */ }
         }
@@ -157,8 +702,71 @@ impl MarkedEventReceiver for YamlLoader {
     }
 }
 
-impl YamlLoader {
-    fn insert_new_node(&mut self, node: (Yaml, usize)) {
+impl<Node: LoadableYamlNode> YamlLoader<Node> {
+    fn new(options: LoaderOptions) -> Self {
+        YamlLoader {
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            map_stack: Vec::new(),
+            anchor_map: BTreeMap::new(),
+            options,
+            error: None,
+            node_count: 0,
+            starts: Vec::new(),
+            anchor_sizes: BTreeMap::new(),
+            expanded: 0,
+        }
+    }
+
+    // returns the start, custom tag and expanded size of the collection
+    fn end_collection(&mut self, aid: usize) -> (Marker, Option<String>, usize) {
+        let (start, mark, tag) = self.starts.pop().unwrap();
+        let size = self.node_count - start;
+        if aid > 0 {
+            self.anchor_sizes.insert(aid, size);
+        }
+        (mark, tag, size)
+    }
+
+    // Build a node with an application-specific tag, using its constructor
+    // if there is one. `size` is the number of nodes in `node` with its
+    // aliases expanded.
+    fn construct(
+        &mut self,
+        tag: String,
+        node: Node,
+        size: usize,
+        mark: Marker,
+        end: Marker,
+    ) -> Result<Node, ScanError> {
+        match self.options.constructors.get(&tag) {
+            Some(constructor) => {
+                if Node::expands_aliases() {
+                    self.expanded = self.expanded.saturating_add(size);
+                    if let Some(max) = self.options.max_alias_expansion {
+                        if self.expanded > max {
+                            return Err(ScanError::limit_exceeded(mark, Limit::AliasExpansion));
+                        }
+                    }
+                }
+                let yaml = constructor(node.into_bare_yaml(), mark)?;
+                Ok(Node::from_bare_yaml(yaml, mark, end))
+            }
+            None => Ok(load_collection_type(&tag, node, mark, end)?.into_tagged(tag)),
+        }
+    }
+
+    // whether the next node is the key of a mapping entry
+    fn at_mapping_key(&self) -> bool {
+        match self.doc_stack.last() {
+            Some((parent, _)) => {
+                parent.hash_ref().is_some() && self.map_stack.last().unwrap().key.is_none()
+            }
+            None => false,
+        }
+    }
+
+    fn insert_new_node(&mut self, node: (Node, usize), mark: Marker) {
         // valid anchor id starts from 1
         if node.1 > 0 {
             self.anchor_map.insert(node.1, node.0.clone());
@@ -166,36 +774,270 @@ impl YamlLoader {
         if self.doc_stack.is_empty() {
             self.doc_stack.push(node);
         } else {
-            let parent = self.doc_stack.last_mut().unwrap();
-            match *parent {
-                (Yaml::Array(ref mut v), _) => v.push(node.0),
-                (Yaml::Hash(ref mut h), _) => {
-                    let cur_key = self.key_stack.last_mut().unwrap();
-                    // current node is a key
-                    if cur_key.is_badvalue() {
-                        *cur_key = node.0;
+            let parent = &mut self.doc_stack.last_mut().unwrap().0;
+            if let Some(v) = parent.array_mut() {
+                v.push(node.0);
+            } else if let Some(h) = parent.hash_mut() {
+                let state = self.map_stack.last_mut().unwrap();
+                match state.key.take() {
+                    // current node is the value of a merge key
+                    Some(_) if state.merge => {
+                        state.merge = false;
+                        state.merges.push((h.len(), node.0, mark));
+                    }
                     // current node is a value
-                    } else {
-                        let mut newkey = Yaml::BadValue;
-                        mem::swap(&mut newkey, cur_key);
-                        h.insert(newkey, node.0);
+                    Some(newkey) => match self.options.duplicate_keys {
+                        DuplicateKeys::LastWins => {
+                            h.insert(newkey, node.0);
+                        }
+                        DuplicateKeys::FirstWins => {
+                            h.entry(newkey).or_insert(node.0);
+                        }
+                        DuplicateKeys::Error => {
+                            if let Some(&original) = state.key_marks.get(&newkey) {
+                                self.error =
+                                    Some(ScanError::duplicate_key(state.key_mark, original));
+                                return;
+                            }
+                            state.key_marks.insert(newkey.clone(), state.key_mark);
+                            h.insert(newkey, node.0);
+                        }
+                        DuplicateKeys::KeepAll => {
+                            if let Some(old) = h.get_mut(&newkey) {
+                                if !state.repeated.contains(&newkey) {
+                                    let start = state.key_marks[&newkey];
+                                    let array = Yaml::Array(Vec::new());
+                                    let array = Node::from_bare_yaml(array, start, mark);
+                                    let first = mem::replace(old, array);
+                                    old.array_mut().unwrap().push(first);
+                                    state.repeated.insert(newkey);
+                                }
+                                old.set_end(mark);
+                                old.array_mut().unwrap().push(node.0);
+                            } else {
+                                state.key_marks.insert(newkey.clone(), state.key_mark);
+                                h.insert(newkey, node.0);
+                            }
+                        }
+                    },
+                    // current node is a key
+                    None => {
+                        state.key = Some(node.0);
+                        state.key_mark = mark;
                     }
                 }
-                _ => unreachable!(),
+            } else {
+                unreachable!()
             }
         }
     }
 
+    /// Load documents built of any node type, such as `Yaml` or
+    /// `MarkedYaml`, with the given options.
+    ///
+    /// ```
+    /// use yaml_rust::yaml::LoaderOptions;
+    /// use yaml_rust::scanner::Limit;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut options = LoaderOptions::default();
+    /// options.parser.max_depth = Some(2);
+    /// let err = YamlLoader::<Yaml>::load_with_options("[[[1]]]", &options).unwrap_err();
+    /// assert_eq!(err.limit(), Some(Limit::Depth));
+    /// ```
+    pub fn load_with_options(
+        source: &str,
+        options: &LoaderOptions,
+    ) -> Result<Vec<Node>, ScanError> {
+        Self::load_chars(source.chars(), TEncoding::Utf8, options)
+    }
+
+    /// Load documents from bytes like `load_with_options`, detecting
+    /// whether they are UTF-8, UTF-16 or UTF-32 from the byte order mark or
+    /// the null bytes around the first character. An invalid byte sequence
+    /// is reported with its byte offset.
+    pub fn load_bytes_with_options(
+        source: &[u8],
+        options: &LoaderOptions,
+    ) -> Result<Vec<Node>, ScanError> {
+        let (text, encoding) = encoding::decode(source)?;
+        Self::load_chars(text.chars(), encoding, options)
+    }
+
+    /// Iterate over the documents of a stream, loading each one only when
+    /// it is requested, so that memory use follows the size of the largest
+    /// document rather than of the whole stream. The iterator ends after
+    /// the first error.
+    pub fn documents_with_options<'a>(
+        source: &'a str,
+        options: &LoaderOptions,
+    ) -> Documents<'a, Node> {
+        Documents {
+            parser: Parser::with_options(source.chars(), options.parser),
+            loader: YamlLoader::new(options.clone()),
+            done: false,
+        }
+    }
+
+    fn load_chars<I: Iterator<Item = char>>(
+        source: I,
+        encoding: TEncoding,
+        options: &LoaderOptions,
+    ) -> Result<Vec<Node>, ScanError> {
+        let mut loader = YamlLoader::new(options.clone());
+        let mut parser = Parser::with_options(source, options.parser);
+        parser.set_encoding(encoding);
+        let res = parser.load(&mut loader, true);
+        if let Some(e) = loader.error {
+            return Err(e);
+        }
+        res?;
+        Ok(loader.docs)
+    }
+
+    fn load_nodes(source: &str) -> Result<Vec<Node>, ScanError> {
+        Self::load_with_options(source, &LoaderOptions::default())
+    }
+}
+
+impl YamlLoader {
     pub fn load_from_str(source: &str) -> Result<Vec<Yaml>, ScanError> {
-        let mut loader = YamlLoader {
-            docs: Vec::new(),
-            doc_stack: Vec::new(),
-            key_stack: Vec::new(),
-            anchor_map: BTreeMap::new(),
+        Self::load_nodes(source)
+    }
+
+    /// Iterate over the documents of a stream, see
+    /// `documents_with_options`.
+    ///
+    /// ```
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let mut docs = YamlLoader::documents("a: 1\n---\n[x\n---\nb: 2");
+    /// assert_eq!(docs.next().unwrap().unwrap()["a"].as_i64(), Some(1));
+    /// assert!(docs.next().unwrap().is_err());
+    /// assert!(docs.next().is_none());
+    /// ```
+    pub fn documents(source: &str) -> Documents<'_> {
+        Self::documents_with_options(source, &LoaderOptions::default())
+    }
+
+    /// Load documents from UTF-8, UTF-16 or UTF-32 bytes, see
+    /// `load_bytes_with_options`.
+    ///
+    /// ```
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let utf16le = b"a\0:\0 \x001\0";
+    /// let docs = YamlLoader::load_from_bytes(utf16le).unwrap();
+    /// assert_eq!(docs[0]["a"].as_i64(), Some(1));
+    /// ```
+    pub fn load_from_bytes(source: &[u8]) -> Result<Vec<Yaml>, ScanError> {
+        Self::load_bytes_with_options(source, &LoaderOptions::default())
+    }
+
+    /// Read a whole stream, such as a file, and load it like
+    /// `load_from_bytes`.
+    pub fn load_from_reader<R: io::Read>(mut reader: R) -> Result<Vec<Yaml>, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(Self::load_from_bytes(&bytes)?)
+    }
+}
+
+/// An iterator over the documents of a stream, see
+/// `YamlLoader::documents_with_options`.
+pub struct Documents<'a, Node: LoadableYamlNode = Yaml> {
+    parser: Parser<str::Chars<'a>>,
+    loader: YamlLoader<Node>,
+    done: bool,
+}
+
+impl<'a, Node: LoadableYamlNode> Iterator for Documents<'a, Node> {
+    type Item = Result<Node, ScanError>;
+
+    fn next(&mut self) -> Option<Result<Node, ScanError>> {
+        if self.done {
+            return None;
+        }
+        let res = self.parser.load(&mut self.loader, false);
+        let res = match self.loader.error.take() {
+            Some(e) => Err(e),
+            None => res,
         };
-        let mut parser = Parser::new(source.chars());
-        parser.load(&mut loader, true)?;
-        Ok(loader.docs)
+        match res.map(|()| self.loader.docs.pop()) {
+            Ok(Some(doc)) => Some(Ok(doc)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// An error from `YamlLoader::load_from_reader`.
+#[derive(Debug)]
+pub enum LoadError {
+    /// Reading the stream failed.
+    Io(io::Error),
+    /// The stream is not valid YAML, or not validly encoded.
+    Scan(ScanError),
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io(ref err) => Some(err),
+            LoadError::Scan(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => fmt::Display::fmt(err, formatter),
+            LoadError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ScanError> for LoadError {
+    fn from(err: ScanError) -> Self {
+        LoadError::Scan(err)
+    }
+}
+
+impl YamlLoader<MarkedYaml> {
+    /// Load documents like `load_from_str`, keeping the start and end
+    /// `Marker` of every node.
+    ///
+    /// ```
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let docs = YamlLoader::load_marked_from_str("name: x\nport: abc").unwrap();
+    /// let port = docs[0].get("port").unwrap();
+    /// assert_eq!(port.start.line(), 2);
+    /// assert_eq!(port.start.col(), 6);
+    /// ```
+    pub fn load_marked_from_str(source: &str) -> Result<Vec<MarkedYaml>, ScanError> {
+        Self::load_nodes(source)
+    }
+}
+
+impl YamlLoader<SharedYaml> {
+    /// Load documents like `load_from_str`, but let aliases share the node
+    /// of their anchor instead of copying it.
+    pub fn load_shared_from_str(source: &str) -> Result<Vec<SharedYaml>, ScanError> {
+        Self::load_nodes(source)
     }
 }
 
@@ -234,17 +1076,47 @@ pub fn $name(self) -> Option<$t> {
 
 impl Yaml {
     define_as!(as_bool, bool, Boolean);
-    define_as!(as_i64, i64, Integer);
 
     define_as_ref!(as_str, &str, String);
     define_as_ref!(as_hash, &Hash, Hash);
     define_as_ref!(as_vec, &Array, Array);
+    define_as_ref!(as_binary, &Vec<u8>, Binary);
+    define_as_ref!(as_number, &Number, Number);
 
     define_into!(into_bool, bool, Boolean);
-    define_into!(into_i64, i64, Integer);
     define_into!(into_string, String, String);
     define_into!(into_hash, Hash, Hash);
     define_into!(into_vec, Array, Array);
+    define_into!(into_binary, Vec<u8>, Binary);
+
+    /// The value of an `Integer`, or of an integer `Number` that fits in an
+    /// `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Yaml::Integer(i) => Some(i),
+            Yaml::Number(ref n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    pub fn into_i64(self) -> Option<i64> {
+        self.as_i64()
+    }
+
+    /// The value of an `Integer`, a `BigInteger` or an integer `Number`.
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Yaml::Integer(i) => Some(i128::from(i)),
+            Yaml::BigInteger(i) => Some(i),
+            Yaml::Number(ref n) => n.as_i128(),
+            _ => None,
+        }
+    }
+
+    /// The value of any integer, if it fits in a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i128().and_then(|i| u64::try_from(i).ok())
+    }
 
     pub fn is_null(&self) -> bool {
         match *self {
@@ -267,16 +1139,22 @@ impl Yaml {
         }
     }
 
+    /// The value of a `Real`, or of a float `Number`.
     pub fn as_f64(&self) -> Option<f64> {
         match *self {
             Yaml::Real(ref v) => parse_f64(v),
+            Yaml::Number(ref n) if n.is_float() => Some(n.as_f64()),
             _ => None,
         }
     }
 
     pub fn into_f64(self) -> Option<f64> {
-        match self {
-            Yaml::Real(ref v) => parse_f64(v),
+        self.as_f64()
+    }
+
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match *self {
+            Yaml::Timestamp(ref v) => Timestamp::parse(v),
             _ => None,
         }
     }
@@ -288,25 +1166,20 @@ impl Yaml {
     // This function falls back to Yaml::String if nothing else matches.
     pub fn from_str(v: &str) -> Yaml {
         if v.starts_with("0x") {
-            if let Ok(i) = i64::from_str_radix(&v[2..], 16) {
-                return Yaml::Integer(i);
+            if let Some(i) = parse_integer(&v[2..], 16) {
+                return i;
             }
         }
         if v.starts_with("0o") {
-            if let Ok(i) = i64::from_str_radix(&v[2..], 8) {
-                return Yaml::Integer(i);
-            }
-        }
-        if v.starts_with('+') {
-            if let Ok(i) = v[1..].parse::<i64>() {
-                return Yaml::Integer(i);
+            if let Some(i) = parse_integer(&v[2..], 8) {
+                return i;
             }
         }
         match v {
             "~" | "null" => Yaml::Null,
             "true" => Yaml::Boolean(true),
             "false" => Yaml::Boolean(false),
-            _ if v.parse::<i64>().is_ok() => Yaml::Integer(v.parse::<i64>().unwrap()),
+            _ if parse_integer(v, 10).is_some() => parse_integer(v, 10).unwrap(),
             // try parsing as f64
             _ if parse_f64(v).is_some() => Yaml::Real(v.to_owned()),
             _ => Yaml::String(v.to_owned()),
@@ -342,6 +1215,158 @@ impl Index<usize> for Yaml {
     }
 }
 
+// A null becomes an empty mapping when it is written to by key, so that
+// nested mappings can be built by assignment.
+fn hash_mut(node: &mut Yaml) -> Option<&mut Hash> {
+    if node.is_null() {
+        *node = Yaml::Hash(Hash::new());
+    }
+    match *node {
+        Yaml::Hash(ref mut h) => Some(h),
+        _ => None,
+    }
+}
+
+pub(crate) fn kind(node: &Yaml) -> &'static str {
+    match *node {
+        Yaml::Array(_) => "sequence",
+        Yaml::Hash(_) => "mapping",
+        _ => "scalar",
+    }
+}
+
+impl<'a> IndexMut<&'a str> for Yaml {
+    /// Turns a null into an empty mapping, and inserts a null for a missing
+    /// key, so `doc["a"]["b"] = value` creates the mappings it needs.
+    ///
+    /// Panics if the node is neither a mapping nor a null.
+    fn index_mut(&mut self, idx: &'a str) -> &mut Yaml {
+        let kind = kind(self);
+        match hash_mut(self) {
+            Some(h) => h.entry(Yaml::String(idx.to_owned())).or_insert(Yaml::Null),
+            None => panic!("cannot index into a {} with the key `{}`", kind, idx),
+        }
+    }
+}
+
+impl IndexMut<usize> for Yaml {
+    /// Indexes a sequence, or a mapping or a null by an integer key like
+    /// `IndexMut<&str>`.
+    ///
+    /// Panics if the index is out of bounds, or if the node is a scalar.
+    fn index_mut(&mut self, idx: usize) -> &mut Yaml {
+        if let Yaml::Array(ref mut v) = *self {
+            let len = v.len();
+            return match v.get_mut(idx) {
+                Some(v) => v,
+                None => panic!(
+                    "index {} is out of bounds for a sequence of length {}",
+                    idx, len
+                ),
+            };
+        }
+        let kind = kind(self);
+        match hash_mut(self) {
+            Some(h) => h.entry(Yaml::Integer(idx as i64)).or_insert(Yaml::Null),
+            None => panic!("cannot index into a {} with the index {}", kind, idx),
+        }
+    }
+}
+
+/// An entry of a mapping or a sequence, which may be vacant, from
+/// `Yaml::entry`.
+pub struct Entry<'a> {
+    inner: EntryInner<'a>,
+}
+
+enum EntryInner<'a> {
+    Hash(linked_hash_map::Entry<'a, Yaml, Yaml>),
+    Array(&'a mut Array, usize),
+}
+
+impl<'a> Entry<'a> {
+    /// Whether the entry has a value.
+    pub fn is_occupied(&self) -> bool {
+        match self.inner {
+            EntryInner::Hash(linked_hash_map::Entry::Occupied(_)) => true,
+            EntryInner::Hash(linked_hash_map::Entry::Vacant(_)) => false,
+            EntryInner::Array(ref v, i) => i < v.len(),
+        }
+    }
+
+    /// The value of the entry, inserting `default` if it is vacant.
+    pub fn or_insert(self, default: Yaml) -> &'a mut Yaml {
+        self.or_insert_with(|| default)
+    }
+
+    /// The value of the entry, inserting the result of `default` if it is
+    /// vacant. A sequence that is too short is padded with nulls.
+    pub fn or_insert_with<F: FnOnce() -> Yaml>(self, default: F) -> &'a mut Yaml {
+        match self.inner {
+            EntryInner::Hash(linked_hash_map::Entry::Occupied(e)) => e.into_mut(),
+            EntryInner::Hash(linked_hash_map::Entry::Vacant(e)) => e.insert(default()),
+            EntryInner::Array(v, i) => {
+                if i >= v.len() {
+                    v.resize(i, Yaml::Null);
+                    v.push(default());
+                }
+                &mut v[i]
+            }
+        }
+    }
+
+    /// Calls `f` on the value of the entry, if it has one.
+    pub fn and_modify<F: FnOnce(&mut Yaml)>(mut self, f: F) -> Entry<'a> {
+        match self.inner {
+            EntryInner::Hash(linked_hash_map::Entry::Occupied(ref mut e)) => f(e.get_mut()),
+            EntryInner::Array(ref mut v, i) if i < v.len() => f(&mut v[i]),
+            _ => {}
+        }
+        self
+    }
+}
+
+impl Yaml {
+    /// The entry for `key` in a mapping, or for the index `key` in a
+    /// sequence. A null is turned into an empty mapping first, so entries
+    /// can be chained to build nested documents.
+    ///
+    /// Panics if the node is a scalar, or if it is a sequence and `key` is
+    /// not a non-negative integer.
+    ///
+    /// ```
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut doc = Yaml::Null;
+    /// let key = |k: &str| Yaml::String(k.to_owned());
+    /// doc.entry(key("a"))
+    ///     .or_insert(Yaml::Null)
+    ///     .entry(key("hits"))
+    ///     .and_modify(|n| *n = Yaml::Integer(n.as_i64().unwrap() + 1))
+    ///     .or_insert(Yaml::Integer(1));
+    /// doc["a"]["tags"].entry(Yaml::Integer(1)).or_insert(key("x"));
+    /// assert_eq!(doc, YamlLoader::load_from_str("a: {hits: 1, tags: {1: x}}").unwrap()[0]);
+    /// ```
+    pub fn entry(&mut self, key: Yaml) -> Entry<'_> {
+        if let Yaml::Array(ref mut v) = *self {
+            let i = match key.as_i64().and_then(|i| usize::try_from(i).ok()) {
+                Some(i) => i,
+                None => panic!("cannot index into a sequence with {:?}", key),
+            };
+            return Entry {
+                inner: EntryInner::Array(v, i),
+            };
+        }
+        let kind = kind(self);
+        match hash_mut(self) {
+            Some(h) => Entry {
+                inner: EntryInner::Hash(h.entry(key)),
+            },
+            None => panic!("cannot index into a {} with {:?}", kind, key),
+        }
+    }
+}
+
 impl IntoIterator for Yaml {
     type Item = Yaml;
     type IntoIter = YamlIter;
@@ -365,6 +1390,376 @@ impl Iterator for YamlIter {
     }
 }
 
+/// A YAML node together with the span of source it was loaded from, as
+/// produced by `YamlLoader::load_marked_from_str`.
+///
+/// Markers are ignored when comparing and hashing nodes, so keys can be
+/// looked up without knowing where they were written.
+#[derive(Clone, Debug)]
+pub struct MarkedYaml {
+    /// Where the node starts.
+    pub start: Marker,
+    /// Where the node ends. For collections, this is the marker of the token
+    /// that closes them.
+    pub end: Marker,
+    pub node: MarkedNode,
+}
+
+/// The content of a `MarkedYaml` node.
+#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+pub enum MarkedNode {
+    /// Any node other than a collection or a tagged node. This never holds
+    /// `Yaml::Array`, `Yaml::Hash` or `Yaml::Tagged`.
+    Scalar(Yaml),
+    Array(Vec<MarkedYaml>),
+    Hash(LinkedHashMap<MarkedYaml, MarkedYaml>),
+    /// A node with an application-specific tag, like `Yaml::Tagged`.
+    Tagged(String, Box<MarkedYaml>),
+}
+
+impl PartialEq for MarkedYaml {
+    fn eq(&self, other: &MarkedYaml) -> bool {
+        self.node == other.node
+    }
+}
+
+impl Eq for MarkedYaml {}
+
+impl hash::Hash for MarkedYaml {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.node.hash(state)
+    }
+}
+
+impl LoadableYamlNode for MarkedYaml {
+    fn from_bare_yaml(yaml: Yaml, start: Marker, end: Marker) -> Self {
+        let node = match yaml {
+            Yaml::Array(v) => MarkedNode::Array(
+                v.into_iter()
+                    .map(|x| MarkedYaml::from_bare_yaml(x, start, end))
+                    .collect(),
+            ),
+            Yaml::Hash(h) => MarkedNode::Hash(
+                h.into_iter()
+                    .map(|(k, v)| {
+                        (
+                            MarkedYaml::from_bare_yaml(k, start, end),
+                            MarkedYaml::from_bare_yaml(v, start, end),
+                        )
+                    })
+                    .collect(),
+            ),
+            Yaml::Tagged(tag, v) => {
+                MarkedNode::Tagged(tag, Box::new(MarkedYaml::from_bare_yaml(*v, start, end)))
+            }
+            yaml => MarkedNode::Scalar(yaml),
+        };
+        MarkedYaml { start, end, node }
+    }
+
+    fn array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self.node {
+            MarkedNode::Array(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn hash_mut(&mut self) -> Option<&mut LinkedHashMap<Self, Self>> {
+        match self.node {
+            MarkedNode::Hash(ref mut h) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn array_ref(&self) -> Option<&Vec<Self>> {
+        match self.node {
+            MarkedNode::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn hash_ref(&self) -> Option<&LinkedHashMap<Self, Self>> {
+        match self.node {
+            MarkedNode::Hash(ref h) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        self.node == MarkedNode::Scalar(Yaml::Null)
+    }
+
+    fn into_tagged(self, tag: String) -> Self {
+        MarkedYaml {
+            start: self.start,
+            end: self.end,
+            node: MarkedNode::Tagged(tag, Box::new(self)),
+        }
+    }
+
+    fn into_bare_yaml(self) -> Yaml {
+        self.into_yaml()
+    }
+
+    fn set_end(&mut self, end: Marker) {
+        self.end = end;
+    }
+}
+
+impl MarkedYaml {
+    /// Look up a string key in a hash node.
+    pub fn get(&self, key: &str) -> Option<&MarkedYaml> {
+        match self.node {
+            MarkedNode::Hash(ref h) => {
+                let key = MarkedYaml {
+                    start: self.start,
+                    end: self.start,
+                    node: MarkedNode::Scalar(Yaml::String(key.to_owned())),
+                };
+                h.get(&key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Look up an element of an array node, or an integer key of a hash node.
+    pub fn get_index(&self, idx: usize) -> Option<&MarkedYaml> {
+        match self.node {
+            MarkedNode::Array(ref v) => v.get(idx),
+            MarkedNode::Hash(ref h) => {
+                let key = MarkedYaml {
+                    start: self.start,
+                    end: self.start,
+                    node: MarkedNode::Scalar(Yaml::Integer(idx as i64)),
+                };
+                h.get(&key)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<&Yaml> {
+        match self.node {
+            MarkedNode::Scalar(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Drop the markers and convert into a plain `Yaml` tree.
+    pub fn into_yaml(self) -> Yaml {
+        match self.node {
+            MarkedNode::Scalar(v) => v,
+            MarkedNode::Array(v) => Yaml::Array(v.into_iter().map(MarkedYaml::into_yaml).collect()),
+            MarkedNode::Hash(h) => Yaml::Hash(
+                h.into_iter()
+                    .map(|(k, v)| (k.into_yaml(), v.into_yaml()))
+                    .collect(),
+            ),
+            MarkedNode::Tagged(tag, v) => Yaml::Tagged(tag, Box::new(v.into_yaml())),
+        }
+    }
+}
+
+/// A YAML node that shares anchored subtrees instead of copying them, as
+/// produced by `YamlLoader::load_shared_from_str`.
+///
+/// Every alias refers to the same reference-counted node as its anchor, so
+/// memory use follows the size of the input rather than the size of the
+/// fully expanded document.
+///
+/// Hashing a node is linear in the size of the input, not of the expanded
+/// document: every node caches its hash, which is computed from the cached
+/// hashes of its children. Comparing checks for the same shared node, and
+/// for equal hashes, before comparing the content.
+#[derive(Clone)]
+pub struct SharedYaml(Rc<Shared>);
+
+struct Shared {
+    node: SharedNode,
+    hash: Cell<Option<u64>>,
+}
+
+/// The content of a `SharedYaml` node.
+#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+pub enum SharedNode {
+    /// Any node other than a collection or a tagged node. This never holds
+    /// `Yaml::Array`, `Yaml::Hash` or `Yaml::Tagged`.
+    Scalar(Yaml),
+    Array(Vec<SharedYaml>),
+    Hash(LinkedHashMap<SharedYaml, SharedYaml>),
+    /// A node with an application-specific tag, like `Yaml::Tagged`.
+    Tagged(String, SharedYaml),
+}
+
+impl PartialEq for SharedYaml {
+    fn eq(&self, other: &SharedYaml) -> bool {
+        self.ptr_eq(other)
+            || (self.hash_value() == other.hash_value() && self.0.node == other.0.node)
+    }
+}
+
+impl Eq for SharedYaml {}
+
+impl hash::Hash for SharedYaml {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash_value())
+    }
+}
+
+impl fmt::Debug for SharedYaml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SharedYaml").field(&self.0.node).finish()
+    }
+}
+
+impl LoadableYamlNode for SharedYaml {
+    fn from_bare_yaml(yaml: Yaml, _: Marker, _: Marker) -> Self {
+        SharedYaml::from_yaml(yaml)
+    }
+
+    // The loader only modifies collections before they are complete, and
+    // thus before any alias can refer to them.
+    fn array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self.node_mut() {
+            Some(SharedNode::Array(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn hash_mut(&mut self) -> Option<&mut LinkedHashMap<Self, Self>> {
+        match self.node_mut() {
+            Some(SharedNode::Hash(h)) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn array_ref(&self) -> Option<&Vec<Self>> {
+        match self.0.node {
+            SharedNode::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn hash_ref(&self) -> Option<&LinkedHashMap<Self, Self>> {
+        match self.0.node {
+            SharedNode::Hash(ref h) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        self.0.node == SharedNode::Scalar(Yaml::Null)
+    }
+
+    fn into_tagged(self, tag: String) -> Self {
+        SharedYaml::new(SharedNode::Tagged(tag, self))
+    }
+
+    fn into_bare_yaml(self) -> Yaml {
+        self.to_yaml()
+    }
+
+    fn expands_aliases() -> bool {
+        true
+    }
+}
+
+impl SharedYaml {
+    fn new(node: SharedNode) -> SharedYaml {
+        SharedYaml(Rc::new(Shared {
+            node,
+            hash: Cell::new(None),
+        }))
+    }
+
+    // Forgets the cached hash, which the change may invalidate.
+    fn node_mut(&mut self) -> Option<&mut SharedNode> {
+        let shared = Rc::get_mut(&mut self.0)?;
+        shared.hash.set(None);
+        Some(&mut shared.node)
+    }
+
+    fn hash_value(&self) -> u64 {
+        if let Some(hash) = self.0.hash.get() {
+            return hash;
+        }
+        let mut hasher = DefaultHasher::new();
+        hash::Hash::hash(&self.0.node, &mut hasher);
+        let hash = hash::Hasher::finish(&hasher);
+        self.0.hash.set(Some(hash));
+        hash
+    }
+
+    /// Build a tree in which nothing is shared yet.
+    pub fn from_yaml(yaml: Yaml) -> SharedYaml {
+        let node = match yaml {
+            Yaml::Array(v) => SharedNode::Array(v.into_iter().map(SharedYaml::from_yaml).collect()),
+            Yaml::Hash(h) => SharedNode::Hash(
+                h.into_iter()
+                    .map(|(k, v)| (SharedYaml::from_yaml(k), SharedYaml::from_yaml(v)))
+                    .collect(),
+            ),
+            Yaml::Tagged(tag, v) => SharedNode::Tagged(tag, SharedYaml::from_yaml(*v)),
+            yaml => SharedNode::Scalar(yaml),
+        };
+        SharedYaml::new(node)
+    }
+
+    pub fn node(&self) -> &SharedNode {
+        &self.0.node
+    }
+
+    /// Look up a string key in a hash node.
+    pub fn get(&self, key: &str) -> Option<&SharedYaml> {
+        match self.0.node {
+            SharedNode::Hash(ref h) => {
+                let key = SharedYaml::new(SharedNode::Scalar(Yaml::String(key.to_owned())));
+                h.get(&key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Look up an element of an array node, or an integer key of a hash node.
+    pub fn get_index(&self, idx: usize) -> Option<&SharedYaml> {
+        match self.0.node {
+            SharedNode::Array(ref v) => v.get(idx),
+            SharedNode::Hash(ref h) => {
+                let key = SharedYaml::new(SharedNode::Scalar(Yaml::Integer(idx as i64)));
+                h.get(&key)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<&Yaml> {
+        match self.0.node {
+            SharedNode::Scalar(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns true if both nodes are the same shared node, e.g. an anchor
+    /// and one of its aliases.
+    pub fn ptr_eq(&self, other: &SharedYaml) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Convert into a plain `Yaml` tree, expanding every alias.
+    ///
+    /// Note that the result can be exponentially larger than the input.
+    pub fn to_yaml(&self) -> Yaml {
+        match self.0.node {
+            SharedNode::Scalar(ref v) => v.clone(),
+            SharedNode::Array(ref v) => Yaml::Array(v.iter().map(SharedYaml::to_yaml).collect()),
+            SharedNode::Hash(ref h) => {
+                Yaml::Hash(h.iter().map(|(k, v)| (k.to_yaml(), v.to_yaml())).collect())
+            }
+            SharedNode::Tagged(ref tag, ref v) => Yaml::Tagged(tag.clone(), Box::new(v.to_yaml())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::f64;
@@ -429,6 +1824,119 @@ a7: 你好
         assert_eq!(out.len(), 3);
     }
 
+    #[test]
+    fn test_documents() {
+        let s = "
+a: &x 1
+b: *x
+---
+- &x [2]
+- *x
+---
+--- !!str
+'c'
+";
+        let docs: Vec<_> = YamlLoader::documents(s).map(Result::unwrap).collect();
+        assert_eq!(docs, YamlLoader::load_from_str(s).unwrap());
+        assert_eq!(docs.len(), 4);
+        assert!(YamlLoader::documents("").next().is_none());
+
+        let marked: Vec<_> =
+            YamlLoader::<MarkedYaml>::documents_with_options(s, &LoaderOptions::default())
+                .map(Result::unwrap)
+                .collect();
+        assert_eq!(marked[3].start.line(), 9);
+
+        // documents before an error are still loaded
+        let mut docs = YamlLoader::documents("1\n---\n2\n---\n[3\n---\n4");
+        assert_eq!(docs.next().unwrap().unwrap().as_i64(), Some(1));
+        assert_eq!(docs.next().unwrap().unwrap().as_i64(), Some(2));
+        assert_eq!(docs.next().unwrap().unwrap_err().marker().line(), 6);
+        assert!(docs.next().is_none());
+
+        let options = LoaderOptions {
+            parser: ParserOptions {
+                max_documents: Some(2),
+                ..ParserOptions::default()
+            },
+            strict: true,
+            ..LoaderOptions::default()
+        };
+        let mut docs = YamlLoader::<Yaml>::documents_with_options("1\n---\n2\n---\n3", &options);
+        assert!(docs.next().unwrap().is_ok());
+        assert!(docs.next().unwrap().is_ok());
+        assert_eq!(
+            docs.next().unwrap().unwrap_err().limit(),
+            Some(Limit::Documents)
+        );
+        assert!(docs.next().is_none());
+        let mut docs = YamlLoader::<Yaml>::documents_with_options("!!int x\n---\n2", &options);
+        assert!(docs.next().unwrap().is_err());
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_index_mut() {
+        let mut doc = Yaml::Null;
+        doc["a"]["b"] = Yaml::Integer(1);
+        doc["a"]["c"] = Yaml::Array(vec![Yaml::Null]);
+        doc["a"]["c"][0] = Yaml::Boolean(true);
+        doc["d"][7] = Yaml::String("x".to_owned());
+        doc["a"]["b"] = Yaml::Integer(2);
+        let expected = YamlLoader::load_from_str("{a: {b: 2, c: [true]}, d: {7: x}}").unwrap();
+        assert_eq!(doc, expected[0]);
+
+        let result = std::panic::catch_unwind(|| {
+            let mut doc = Yaml::Integer(1);
+            doc["a"] = Yaml::Null;
+        });
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(|| {
+            let mut doc = Yaml::Array(vec![]);
+            doc[0] = Yaml::Null;
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_entry() {
+        let key = |k: &str| Yaml::String(k.to_owned());
+        let mut doc = YamlLoader::load_from_str("{n: 1, list: [a]}")
+            .unwrap()
+            .remove(0);
+        for _ in 0..2 {
+            doc.entry(key("n"))
+                .and_modify(|n| *n = Yaml::Integer(n.as_i64().unwrap() * 10))
+                .or_insert(Yaml::Integer(0));
+            doc.entry(key("m"))
+                .and_modify(|n| *n = Yaml::Integer(n.as_i64().unwrap() * 10))
+                .or_insert(Yaml::Integer(1));
+        }
+        assert!(doc.entry(key("n")).is_occupied());
+        assert!(!doc.entry(key("z")).is_occupied());
+
+        let list = doc.entry(key("list")).or_insert_with(|| unreachable!());
+        assert!(list.entry(Yaml::Integer(0)).is_occupied());
+        list.entry(Yaml::Integer(0)).or_insert(key("x"));
+        list.entry(Yaml::Integer(2)).or_insert(key("c"));
+        doc.entry(key("new"))
+            .or_insert(Yaml::Null)
+            .entry(key("nested"))
+            .or_insert(Yaml::Array(vec![]))
+            .entry(Yaml::Integer(0))
+            .or_insert(Yaml::Integer(1));
+        let expected =
+            YamlLoader::load_from_str("{n: 100, list: [a, ~, c], m: 10, new: {nested: [1]}}")
+                .unwrap();
+        assert_eq!(doc, expected[0]);
+
+        let result = std::panic::catch_unwind(|| {
+            let mut doc = Yaml::Array(vec![]);
+            doc.entry(key("a"));
+        });
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_anchor() {
         let s = "
@@ -442,6 +1950,673 @@ a2: *DEFAULT
         assert_eq!(doc["a2"]["b1"].as_i64().unwrap(), 4);
     }
 
+    #[test]
+    fn test_marked() {
+        let s = "
+a: [1, 'two']
+b:
+  c: &x 3
+  d: *x
+e:
+  - f: 1
+";
+        let out = YamlLoader::load_marked_from_str(s).unwrap();
+        let doc = &out[0];
+        // a block mapping starts at its first key
+        assert_eq!((doc.start.line(), doc.start.col()), (2, 0));
+        let b = doc.get("b").unwrap();
+        assert_eq!((b.start.line(), b.start.col()), (4, 2));
+        let e = doc.get("e").unwrap();
+        assert_eq!((e.start.line(), e.start.col()), (7, 2));
+        let f = e.get_index(0).unwrap();
+        assert_eq!((f.start.line(), f.start.col()), (7, 4));
+
+        let a = doc.get("a").unwrap();
+        assert_eq!((a.start.line(), a.start.col()), (2, 3));
+        assert_eq!((a.end.line(), a.end.col()), (2, 12));
+        let two = a.get_index(1).unwrap();
+        assert_eq!(two.as_scalar(), Some(&Yaml::String("two".to_owned())));
+        assert_eq!((two.start.col(), two.end.col()), (7, 12));
+
+        let c = doc.get("b").unwrap().get("c").unwrap();
+        assert_eq!((c.start.line(), c.start.col(), c.end.col()), (4, 8, 9));
+        let d = doc.get("b").unwrap().get("d").unwrap();
+        assert_eq!(d.start, c.start);
+        assert!(doc.get("g").is_none());
+
+        let plain = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out.into_iter().next().unwrap().into_yaml(), plain[0]);
+    }
+
+    #[test]
+    fn test_shared() {
+        let mut s = String::from("a0: &a0 [x, x, x, x, x, x, x, x, x, x]\n");
+        for i in 1..40 {
+            s += &format!("a{}: &a{} [*a{j}, *a{j}, *a{j}, *a{j}, *a{j}, *a{j}, *a{j}, *a{j}, *a{j}, *a{j}]\n", i, i, j = i - 1);
+        }
+        let out = YamlLoader::load_shared_from_str(&s).unwrap();
+        let doc = &out[0];
+        let a39 = doc.get("a39").unwrap();
+        let a38 = doc.get("a38").unwrap();
+        assert!(a39.get_index(0).unwrap().ptr_eq(a38));
+        assert!(a39.get_index(9).unwrap().ptr_eq(a38));
+        let x = doc.get("a0").unwrap().get_index(3).unwrap();
+        assert_eq!(x.as_scalar(), Some(&Yaml::String("x".to_owned())));
+
+        // hashing and comparing an alias key doesn't expand it
+        s += "? *a39\n: key\n";
+        let out = YamlLoader::load_shared_from_str(&s).unwrap();
+        let a39 = out[0].get("a39").unwrap();
+        match *out[0].node() {
+            SharedNode::Hash(ref h) => {
+                assert_eq!(h[a39].as_scalar(), Some(&Yaml::String("key".to_owned())));
+            }
+            _ => panic!("expected a mapping"),
+        }
+
+        let s = "
+a1: &DEFAULT
+    b1: 4
+a2: *DEFAULT
+";
+        let shared = YamlLoader::load_shared_from_str(s).unwrap();
+        let plain = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(shared[0].to_yaml(), plain[0]);
+    }
+
+    #[test]
+    fn test_limits() {
+        fn limit_of(s: &str, options: &LoaderOptions) -> Option<Limit> {
+            match YamlLoader::<Yaml>::load_with_options(s, options) {
+                Ok(_) => None,
+                Err(e) => e.limit(),
+            }
+        }
+
+        let parser_options = |p| LoaderOptions {
+            parser: p,
+            ..LoaderOptions::default()
+        };
+
+        let options = parser_options(ParserOptions {
+            max_depth: Some(2),
+            ..ParserOptions::default()
+        });
+        assert_eq!(limit_of("a: [1, 2]", &options), None);
+        assert_eq!(limit_of("a:\n  b:\n    c: 1", &options), Some(Limit::Depth));
+
+        let options = parser_options(ParserOptions {
+            max_scalar_length: Some(5),
+            ..ParserOptions::default()
+        });
+        assert_eq!(limit_of("[abcde, 'abcde']", &options), None);
+        assert_eq!(limit_of("a: |\n  abcd\n", &options), None);
+        assert_eq!(limit_of("abcdef", &options), Some(Limit::ScalarLength));
+        assert_eq!(limit_of("\"abc def\"", &options), Some(Limit::ScalarLength));
+        assert_eq!(
+            limit_of("|\n  abc\n  def", &options),
+            Some(Limit::ScalarLength)
+        );
+
+        let options = parser_options(ParserOptions {
+            max_nodes: Some(4),
+            ..ParserOptions::default()
+        });
+        assert_eq!(limit_of("[1, 2, 3]", &options), None);
+        assert_eq!(limit_of("[1, 2, 3, 4]", &options), Some(Limit::Nodes));
+
+        let options = parser_options(ParserOptions {
+            max_documents: Some(2),
+            ..ParserOptions::default()
+        });
+        assert_eq!(limit_of("--- 1\n--- 2", &options), None);
+        assert_eq!(
+            limit_of("--- 1\n--- 2\n--- 3", &options),
+            Some(Limit::Documents)
+        );
+
+        let options = LoaderOptions {
+            max_alias_expansion: Some(100),
+            ..LoaderOptions::default()
+        };
+        let mut s = String::from("a0: &a0 [x, x, x, x, x, x, x, x, x]\n");
+        assert_eq!(limit_of(&(s.clone() + "b: [*a0, *a0]"), &options), None);
+        for i in 1..10 {
+            s += &format!(
+                "a{0}: &a{0} [*a{1}, *a{1}, *a{1}, *a{1}, *a{1}]\n",
+                i,
+                i - 1
+            );
+        }
+        let err = YamlLoader::<Yaml>::load_with_options(&s, &options).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::AliasExpansion));
+        assert_eq!(err.marker().line(), 3);
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let s = "
+base: &base {a: 1, b: 2}
+other: &other {b: 3, c: 4}
+single:
+  x: 0
+  <<: *base
+  a: 5
+list:
+  <<: [*other, *base]
+tagged: {!!merge <<: *base}
+quoted: {'<<': *base}
+";
+        let options = LoaderOptions {
+            merge_keys: true,
+            ..LoaderOptions::default()
+        };
+        let out = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        let keys: Vec<_> = doc["single"]
+            .as_hash()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["x", "b", "a"]);
+        assert_eq!(doc["single"]["a"].as_i64(), Some(5));
+        assert_eq!(doc["single"]["b"].as_i64(), Some(2));
+        assert_eq!(doc["list"]["a"].as_i64(), Some(1));
+        assert_eq!(doc["list"]["b"].as_i64(), Some(3));
+        assert_eq!(doc["list"]["c"].as_i64(), Some(4));
+        assert_eq!(doc["tagged"]["a"].as_i64(), Some(1));
+        assert_eq!(doc["quoted"]["<<"]["a"].as_i64(), Some(1));
+
+        let out = YamlLoader::load_from_str("a: {<<: {b: 1}}").unwrap();
+        assert_eq!(out[0]["a"]["<<"]["b"].as_i64(), Some(1));
+
+        let marked = YamlLoader::<MarkedYaml>::load_with_options(s, &options).unwrap();
+        assert_eq!(marked[0].clone().into_yaml(), *doc);
+
+        let err =
+            YamlLoader::<Yaml>::load_with_options("a: 1\n<<: [{b: 1}, 2]", &options).unwrap_err();
+        assert_eq!(err.marker().line(), 2);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let s = "a: 1\nb: 2\na: 3\nc: {x: 4}\na: [5]";
+        let load = |duplicate_keys| {
+            let options = LoaderOptions {
+                duplicate_keys,
+                ..LoaderOptions::default()
+            };
+            YamlLoader::<Yaml>::load_with_options(s, &options)
+        };
+
+        let out = load(DuplicateKeys::LastWins).unwrap();
+        assert_eq!(out, YamlLoader::load_from_str(s).unwrap());
+        assert_eq!(out[0]["a"][0].as_i64(), Some(5));
+
+        let out = load(DuplicateKeys::FirstWins).unwrap();
+        assert_eq!(out[0]["a"].as_i64(), Some(1));
+        let keys: Vec<_> = out[0]
+            .as_hash()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+
+        let out = load(DuplicateKeys::KeepAll).unwrap();
+        assert_eq!(
+            out[0]["a"],
+            YamlLoader::load_from_str("[1, 3, [5]]").unwrap()[0]
+        );
+        assert_eq!(out[0]["b"].as_i64(), Some(2));
+
+        let err = load(DuplicateKeys::Error).unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+        assert_eq!(err.original_marker().unwrap().line(), 1);
+        assert_eq!(
+            err.to_string(),
+            "duplicate key, first defined at line 1 column 1 at line 3 column 1"
+        );
+
+        let options = LoaderOptions {
+            duplicate_keys: DuplicateKeys::KeepAll,
+            ..LoaderOptions::default()
+        };
+        let out =
+            YamlLoader::<MarkedYaml>::load_with_options("- {k: a,\n   k: b}", &options).unwrap();
+        let values = out[0].get_index(0).unwrap().get("k").unwrap();
+        assert_eq!(values.start.line(), 1);
+        assert_eq!(values.start.col(), 3);
+        assert_eq!(
+            values.get_index(1).unwrap().as_scalar().unwrap().as_str(),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        let s = "[yes, 0b11, 1_000, !!bool off, !!int 0o17, 0o17]";
+        let load = |schema| {
+            let options = LoaderOptions {
+                schema,
+                ..LoaderOptions::default()
+            };
+            YamlLoader::<Yaml>::load_with_options(s, &options).unwrap()
+        };
+
+        let out = load(None);
+        assert_eq!(out, YamlLoader::load_from_str(s).unwrap());
+        assert_eq!(out[0][3], Yaml::BadValue);
+
+        let out = load(Some(Schema::Yaml11));
+        assert_eq!(out[0][0], Yaml::Boolean(true));
+        assert_eq!(out[0][1], Yaml::Integer(3));
+        assert_eq!(out[0][2], Yaml::Integer(1000));
+        assert_eq!(out[0][3], Yaml::Boolean(false));
+        assert_eq!(out[0][4], Yaml::BadValue);
+        assert_eq!(out[0][5], Yaml::String("0o17".to_owned()));
+
+        let out = load(Some(Schema::Core));
+        assert_eq!(out[0][0], Yaml::String("yes".to_owned()));
+        assert_eq!(out[0][3], Yaml::BadValue);
+        assert_eq!(out[0][4], Yaml::Integer(15));
+
+        let out = load(Some(Schema::Failsafe));
+        assert_eq!(out[0][1], Yaml::String("0b11".to_owned()));
+        assert_eq!(out[0][4], Yaml::Integer(15));
+    }
+
+    #[test]
+    fn test_tagged() {
+        let s = "
+a: !secret abc
+b: !secret 'abc'
+c: !ref {x: 1}
+d: &seq !e!list [1]
+e: *seq
+f: !!str 1
+g: !!binary aGk=
+h: ! 12
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        let tagged = |tag: &str, v: Yaml| Yaml::Tagged(tag.to_owned(), Box::new(v));
+        assert_eq!(doc["a"], tagged("!secret", Yaml::String("abc".to_owned())));
+        assert_eq!(doc["b"], doc["a"]);
+        assert_eq!(
+            doc["c"],
+            tagged(
+                "!ref",
+                YamlLoader::load_from_str("{x: 1}").unwrap()[0].clone()
+            )
+        );
+        assert_eq!(
+            doc["d"],
+            tagged("!e!list", Yaml::Array(vec![Yaml::Integer(1)]))
+        );
+        assert_eq!(doc["e"], doc["d"]);
+        assert_eq!(doc["f"], Yaml::String("1".to_owned()));
+        assert_eq!(doc["g"], Yaml::Binary(b"hi".to_vec()));
+        assert_eq!(doc["h"], Yaml::String("12".to_owned()));
+
+        let marked = YamlLoader::load_marked_from_str(s).unwrap();
+        assert_eq!(marked[0].clone().into_yaml(), *doc);
+        let c = marked[0].get("c").unwrap();
+        match c.node {
+            MarkedNode::Tagged(ref tag, ref v) => {
+                assert_eq!(tag, "!ref");
+                assert_eq!(v.get("x").unwrap().start.line(), 4);
+            }
+            _ => panic!("expected a tagged node"),
+        }
+
+        let shared = YamlLoader::load_shared_from_str(s).unwrap();
+        assert_eq!(shared[0].to_yaml(), *doc);
+        assert!(shared[0]
+            .get("d")
+            .unwrap()
+            .ptr_eq(shared[0].get("e").unwrap()));
+    }
+
+    #[test]
+    fn test_collection_types() {
+        let s = "
+set: !!set {a, b}
+omap: !!omap
+  - z: 1
+  - a: 2
+pairs: !!pairs [a: 1, a: 2]
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        let tagged = |tag: &str, s: &str| {
+            Yaml::Tagged(
+                tag.to_owned(),
+                Box::new(YamlLoader::load_from_str(s).unwrap()[0].clone()),
+            )
+        };
+        assert_eq!(doc["set"], tagged("!!set", "{a: ~, b: ~}"));
+        assert_eq!(doc["omap"], tagged("!!omap", "{z: 1, a: 2}"));
+        assert_eq!(doc["pairs"], tagged("!!pairs", "[a: 1, a: 2]"));
+
+        let marked = YamlLoader::load_marked_from_str(s).unwrap();
+        assert_eq!(marked[0].clone().into_yaml(), *doc);
+        match marked[0].get("omap").unwrap().node {
+            MarkedNode::Tagged(_, ref v) => {
+                assert_eq!(v.get("a").unwrap().start.line(), 5);
+            }
+            _ => panic!("expected a tagged node"),
+        }
+        let shared = YamlLoader::load_shared_from_str(s).unwrap();
+        assert_eq!(shared[0].to_yaml(), *doc);
+
+        let errors = [
+            ("!!set {a: 1}", "expected a mapping with null values"),
+            ("!!set [a]", "expected a mapping with null values"),
+            (
+                "!!omap {a: 1}",
+                "expected a sequence of single-pair mappings",
+            ),
+            (
+                "!!omap [a: 1, {b: 2, c: 3}]",
+                "expected a sequence of single-pair mappings",
+            ),
+            ("!!omap [a: 1, b: 2, a: 3]", "found a duplicate key"),
+            (
+                "x: !!pairs [a]",
+                "expected a sequence of single-pair mappings",
+            ),
+        ];
+        for &(s, msg) in &errors {
+            let err = YamlLoader::load_from_str(s).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", s, err);
+            // reported where the collection starts
+            assert_eq!(err.marker().col(), s.find(&['[', '{'][..]).unwrap());
+            assert!(YamlLoader::load_shared_from_str(s).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tag_constructors() {
+        let mut options = LoaderOptions::default();
+        options.constructors.insert("!upper", |v, mark| match v {
+            Yaml::String(s) => Ok(Yaml::String(s.to_uppercase())),
+            _ => Err(ScanError::new(mark, "!upper expects a scalar")),
+        });
+        options.constructors.insert("!sum", |v, mark| {
+            let mut sum = 0;
+            for x in v.as_vec().unwrap_or(&Vec::new()) {
+                sum += x
+                    .as_i64()
+                    .ok_or_else(|| ScanError::new(mark, "!sum expects integers"))?;
+            }
+            Ok(Yaml::Integer(sum))
+        });
+        assert!(format!("{:?}", options).contains("\"!sum\""));
+
+        let s = "a: !upper abc\nb: !sum [1, 2, 3]\nc: !other x\nd: !upper '12'";
+        let out = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        assert_eq!(out[0]["a"].as_str(), Some("ABC"));
+        assert_eq!(out[0]["b"].as_i64(), Some(6));
+        assert_eq!(
+            out[0]["c"],
+            Yaml::Tagged("!other".to_owned(), Box::new(Yaml::String("x".to_owned())))
+        );
+        assert_eq!(out[0]["d"].as_str(), Some("12"));
+
+        let marked = YamlLoader::<MarkedYaml>::load_with_options(s, &options).unwrap();
+        let b = marked[0].get("b").unwrap();
+        assert_eq!(b.as_scalar(), Some(&Yaml::Integer(6)));
+        assert_eq!(b.start.line(), 2);
+        assert_eq!(b.start.col(), 8);
+
+        let err = YamlLoader::<Yaml>::load_with_options("a: !sum [1]\nb: !sum [x]", &options)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "!sum expects integers at line 2 column 9");
+        let err = YamlLoader::<Yaml>::load_with_options("- !upper [x]", &options).unwrap_err();
+        assert_eq!(err.marker().col(), 9);
+
+        assert!(options.constructors.remove("!upper"));
+        let out = YamlLoader::<Yaml>::load_with_options("!upper abc", &options).unwrap();
+        assert!(out[0].as_str().is_none());
+
+        // a shared collection is expanded for its constructor, and the copy
+        // counts as alias expansion: 30 nodes for the aliases, 31 for `!len`
+        options.constructors.insert("!len", |v, _| {
+            Ok(Yaml::Integer(v.as_vec().map_or(0, |v| v.len() as i64)))
+        });
+        options.max_alias_expansion = Some(50);
+        let s = "a: &a [1, 1, 1, 1, 1, 1, 1, 1, 1]\nb: [*a, *a, *a]\nc: !len [1, 2]";
+        assert!(YamlLoader::<SharedYaml>::load_with_options(s, &options).is_ok());
+        let s = "a: &a [1, 1, 1, 1, 1, 1, 1, 1, 1]\nb: !len [*a, *a, *a]";
+        let out = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        assert_eq!(out[0]["b"], Yaml::Integer(3));
+        let err = YamlLoader::<SharedYaml>::load_with_options(s, &options).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::AliasExpansion));
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let s = "
+a: 2001-12-14t21:59:43.10-05:00
+b: '2002-12-14'
+c: !!timestamp 2002-12-14
+d: !!timestamp 2002-13-14
+e: 2001-12-14 21:59:43.10 -5
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0]["a"].as_str(), Some("2001-12-14t21:59:43.10-05:00"));
+        assert_eq!(out[0]["c"], Yaml::Timestamp("2002-12-14".to_owned()));
+        assert_eq!(out[0]["d"], Yaml::BadValue);
+
+        let options = LoaderOptions {
+            timestamps: true,
+            ..LoaderOptions::default()
+        };
+        let out = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        let a = out[0]["a"].as_timestamp().unwrap();
+        assert_eq!(a.to_string(), "2001-12-14T21:59:43.1-05:00");
+        assert_eq!(out[0]["b"].as_str(), Some("2002-12-14"));
+        assert_eq!(out[0]["e"].as_timestamp(), Some(a));
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let s = "
+- 9223372036854775807
+- 18446744073709551615
+- -9223372036854775809
+- 0xffffffffffffffffffff
+- !!int 18446744073709551616
+- 170141183460469231731687303715884105728
+- !!int -170141183460469231731687303715884105729
+- 1e30
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc[0], Yaml::Integer(i64::MAX));
+        assert_eq!(doc[1], Yaml::BigInteger(u64::MAX.into()));
+        assert_eq!(doc[1].as_u64(), Some(u64::MAX));
+        assert_eq!(doc[1].as_i64(), None);
+        assert_eq!(doc[2].as_i128(), Some(i128::from(i64::MIN) - 1));
+        assert_eq!(doc[2].as_u64(), None);
+        assert_eq!(doc[3], Yaml::BigInteger(0xffff_ffff_ffff_ffff_ffff));
+        assert_eq!(doc[4].as_i128(), Some(1 << 64));
+        assert_eq!(doc[5], Yaml::BadValue);
+        assert_eq!(doc[6], Yaml::BadValue);
+        assert_eq!(doc[7].as_f64(), Some(1e30));
+
+        let options = LoaderOptions {
+            schema: Some(Schema::Core),
+            ..LoaderOptions::default()
+        };
+        let core = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        assert_eq!(core[0], out[0]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let s = "
+mode: 0o755
+mask: 0xFF
+big: 18446744073709551615
+ratio: 1.50
+int: !!int 12
+float: !!float 1
+quoted: '12'
+word: abc
+";
+        let options = LoaderOptions {
+            numbers: true,
+            ..LoaderOptions::default()
+        };
+        let out = YamlLoader::<Yaml>::load_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        let mode = doc["mode"].as_number().unwrap();
+        assert_eq!((mode.radix(), mode.literal()), (8, "0o755"));
+        assert_eq!(doc["mode"].as_i64(), Some(0o755));
+        assert_eq!(doc["mask"].as_i64(), Some(255));
+        assert_eq!(doc["big"].as_u64(), Some(u64::MAX));
+        assert_eq!(doc["ratio"].as_f64(), Some(1.5));
+        assert_eq!(doc["ratio"].as_i64(), None);
+        assert_eq!(doc["int"].as_i64(), Some(12));
+        assert_eq!(doc["float"].as_f64(), Some(1.0));
+        assert_eq!(doc["quoted"].as_str(), Some("12"));
+        assert_eq!(doc["word"].as_str(), Some("abc"));
+        assert!(doc["mode"] > doc["mask"]);
+
+        let options = LoaderOptions {
+            numbers: true,
+            schema: Some(Schema::Yaml11),
+            ..LoaderOptions::default()
+        };
+        let out = YamlLoader::<Yaml>::load_with_options("[0755, 1_000.5, 1:30]", &options).unwrap();
+        let radixes: Vec<_> = out[0]
+            .as_vec()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_number().unwrap().radix())
+            .collect();
+        assert_eq!(radixes, [8, 10, 60]);
+        assert_eq!(out[0][1].as_f64(), Some(1000.5));
+    }
+
+    #[test]
+    fn test_strict() {
+        let options = LoaderOptions {
+            strict: true,
+            ..LoaderOptions::default()
+        };
+        let load = |s: &str| YamlLoader::<Yaml>::load_with_options(s, &options);
+        let errors = [
+            (
+                "a: !!int abc",
+                "while loading !!int, found an invalid value at line 1 column 10",
+            ),
+            (
+                "- !!bool maybe",
+                "while loading !!bool, found an invalid value at line 1 column 10",
+            ),
+            (
+                "x: 1\ny: !!float 1.2.3",
+                "while loading !!float, found an invalid value at line 2 column 12",
+            ),
+            (
+                "!!null 0",
+                "while loading !!null, found an invalid value at line 1 column 8",
+            ),
+            (
+                "[!!binary '@@']",
+                "while loading !!binary, found an invalid value at line 1 column 11",
+            ),
+            (
+                "!!timestamp 2002-02-30",
+                "while loading !!timestamp, found an invalid value at line 1 column 13",
+            ),
+            (
+                "- 1\n- 999999999999999999999999999999999999999",
+                "while loading an integer, found a value out of range at line 2 column 3",
+            ),
+            (
+                "a: !!int \"abc\"",
+                "while loading !!int, found an invalid value at line 1 column 10",
+            ),
+            (
+                "a: !!float 'x'",
+                "while loading !!float, found an invalid value at line 1 column 12",
+            ),
+            (
+                "- !!bool \"maybe\"",
+                "while loading !!bool, found an invalid value at line 1 column 10",
+            ),
+        ];
+        for &(s, msg) in &errors {
+            assert_eq!(load(s).unwrap_err().to_string(), msg);
+            assert!(YamlLoader::load_from_str(s).is_ok());
+        }
+        assert_eq!(
+            YamlLoader::load_from_str("!!int abc").unwrap()[0],
+            Yaml::BadValue
+        );
+
+        let out = load("[!!int 12, !!bool true, !!str abc, !e!x abc]").unwrap();
+        assert_eq!(out[0][0], Yaml::Integer(12));
+        assert_eq!(out[0][1], Yaml::Boolean(true));
+        let out = load("[!!int '12', !!bool \"true\", !!str '1']").unwrap();
+        assert_eq!(out[0][0], Yaml::Integer(12));
+        assert_eq!(out[0][1], Yaml::Boolean(true));
+        assert_eq!(out[0][2], Yaml::String("1".to_owned()));
+
+        let core = LoaderOptions {
+            schema: Some(Schema::Core),
+            ..options.clone()
+        };
+        let err = YamlLoader::<Yaml>::load_with_options("!!int 1.5", &core).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while loading !!int, found an invalid value at line 1 column 7"
+        );
+    }
+
+    #[test]
+    fn test_load_from_bytes() {
+        let s = "\u{feff}a: [1, é]\n";
+        let expected = YamlLoader::load_from_str("a: [1, é]").unwrap();
+        let utf16be: Vec<u8> = s.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(YamlLoader::load_from_bytes(&utf16be).unwrap(), expected);
+        assert_eq!(
+            YamlLoader::load_from_reader(&utf16be[..]).unwrap(),
+            expected
+        );
+        assert_eq!(YamlLoader::load_from_bytes(s.as_bytes()).unwrap(), expected);
+
+        let marked =
+            YamlLoader::<MarkedYaml>::load_bytes_with_options(&utf16be, &LoaderOptions::default())
+                .unwrap();
+        assert_eq!(marked[0].get("a").unwrap().start.col(), 3);
+
+        let err = YamlLoader::load_from_bytes(b"a: \xc3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid UTF-8 byte sequence at byte offset 3 at line 1 column 4"
+        );
+        match YamlLoader::load_from_reader(&b"a: [1"[..]).unwrap_err() {
+            LoadError::Scan(e) => assert_eq!(e.marker().line(), 2),
+            e => panic!("unexpected error {}", e),
+        }
+
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+        let err = YamlLoader::load_from_reader(Failing).unwrap_err();
+        assert_eq!(err.to_string(), "disk on fire");
+        assert!(err.source().is_some());
+    }
+
     #[test]
     fn test_bad_anchor() {
         let s = "